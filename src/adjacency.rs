@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::fs;
+
+use crate::map::parse_default_map;
+use crate::provincemap::{parse_province_map, ProvinceMap};
+
+// A row of map/adjacencies.csv, connects two provinces that don't share a border
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adjacency {
+    pub from: u64,
+    pub to: u64,
+    // sea, land, canal, river or lake
+    pub kind: String,
+    pub through: u64,
+    pub comment: String,
+}
+
+#[derive(Debug, Default)]
+pub struct AdjacencyGraph {
+    pub neighbours: BTreeMap<u64, BTreeSet<u64>>,
+    pub adjacencies: Vec<Adjacency>,
+    pub seas: HashSet<u64>,
+}
+
+impl AdjacencyGraph {
+    pub fn new(map: &ProvinceMap, adjacencies: Vec<Adjacency>, seas: HashSet<u64>) -> AdjacencyGraph {
+        let mut graph = AdjacencyGraph { neighbours: BTreeMap::new(), adjacencies: vec![], seas };
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let id = map.id_at(x, y);
+                if x + 1 < map.width {
+                    graph.connect(id, map.id_at(x + 1, y));
+                }
+                if y + 1 < map.height {
+                    graph.connect(id, map.id_at(x, y + 1));
+                }
+            }
+        }
+        for adjacency in adjacencies.iter() {
+            if adjacency.kind == "impassable" {
+                graph.disconnect(adjacency.from, adjacency.to);
+            } else {
+                graph.connect(adjacency.from, adjacency.to);
+            }
+        }
+        graph.adjacencies = adjacencies;
+        graph
+    }
+
+    fn connect(&mut self, a: u64, b: u64) {
+        // 0 is every pixel without a definition
+        if a != b && a != 0 && b != 0 {
+            self.neighbours.entry(a).or_default().insert(b);
+            self.neighbours.entry(b).or_default().insert(a);
+        }
+    }
+
+    fn disconnect(&mut self, a: u64, b: u64) {
+        if let Some(n) = self.neighbours.get_mut(&a) {
+            n.remove(&b);
+        }
+        if let Some(n) = self.neighbours.get_mut(&b) {
+            n.remove(&a);
+        }
+    }
+
+    pub fn neighbours(&self, id: u64) -> Vec<u64> {
+        self.neighbours.get(&id).map(|n| n.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn is_sea(&self, id: u64) -> bool {
+        self.seas.contains(&id)
+    }
+
+    // A land province next to a sea province
+    pub fn is_coastal(&self, id: u64) -> bool {
+        !self.is_sea(id) && self.neighbours(id).iter().any(|n| self.is_sea(*n))
+    }
+
+    // Fewest provinces between the two, both ends included
+    pub fn shortest_path(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        let mut previous: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+        let mut seen = HashSet::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![to];
+                let mut node = to;
+                while let Some(prev) = previous.get(&node) {
+                    path.push(*prev);
+                    node = *prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.neighbours(current) {
+                if seen.insert(next) {
+                    previous.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    // Groups of provinces that can reach each other without leaving `include`
+    pub fn connected_components<F: Fn(u64) -> bool>(&self, include: F) -> Vec<Vec<u64>> {
+        let mut components = vec![];
+        let mut seen = HashSet::new();
+        for start in self.neighbours.keys().copied().filter(|id| include(*id)) {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut component = vec![];
+            let mut stack = vec![start];
+            while let Some(current) = stack.pop() {
+                component.push(current);
+                for next in self.neighbours(current) {
+                    if include(next) && seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    // Provinces outside of the set that touch it, e.g. everything bordering the Serpentspine
+    pub fn bordering(&self, provinces: &HashSet<u64>) -> BTreeSet<u64> {
+        provinces.iter()
+            .flat_map(|id| self.neighbours(*id))
+            .filter(|id| !provinces.contains(id))
+            .collect()
+    }
+}
+
+// From;To;Type;Through;start_x;start_y;stop_x;stop_y;adjacency_rule_name;Comment
+pub fn parse_adjacencies(data: &[u8]) -> Vec<Adjacency> {
+    let data = String::from_utf8_lossy(data);
+    let mut adjacencies = vec![];
+    for line in data.lines() {
+        let parts = line.split(';').collect::<Vec<_>>();
+        if parts.len() < 4 {
+            continue;
+        }
+        // the header and the -1 terminator don't parse
+        if let (Ok(from), Ok(to)) = (parts[0].trim().parse::<u64>(), parts[1].trim().parse::<u64>()) {
+            adjacencies.push(Adjacency {
+                from,
+                to,
+                kind: parts[2].trim().to_string(),
+                through: parts[3].trim().parse().unwrap_or(0),
+                comment: parts.last().unwrap_or(&"").trim().to_string(),
+            });
+        }
+    }
+    adjacencies
+}
+
+pub fn parse_adjacency_graph() -> AdjacencyGraph {
+    let adjacencies = fs::read("./anbennar/map/adjacencies.csv").expect("error reading adjacencies.csv");
+    let default_map = parse_default_map();
+    AdjacencyGraph::new(
+        &parse_province_map(),
+        parse_adjacencies(adjacencies.as_slice()),
+        default_map.sea_starts.into_iter().collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::provincemap::Image;
+
+    use super::*;
+
+    #[test]
+    fn test_adjacency_graph() {
+        // 1 1 2 | 3
+        // 4 4 2 | 3   with 3 only reachable through the strait
+        let mut image = Image::new(4, 2);
+        let colours = [[1, 0, 0], [2, 0, 0], [3, 0, 0], [4, 0, 0]];
+        for (x, y, id) in [(0, 0, 1), (1, 0, 1), (2, 0, 2), (3, 0, 0), (0, 1, 4), (1, 1, 4), (2, 1, 2), (3, 1, 0)] {
+            image.set(x, y, if id == 0 { [9, 9, 9] } else { colours[id - 1] });
+        }
+        let definitions = (1..=4).map(|id| (colours[id as usize - 1], id)).collect::<HashMap<_, _>>();
+        let map = ProvinceMap::new(&image, &definitions);
+        let adjacencies = parse_adjacencies(b"From;To;Type;Through;start_x;start_y;stop_x;stop_y;adjacency_rule_name;Comment\n2;3;sea;4;1;1;1;1;;Strait\n-1;-1;;-1;-1;-1;-1;-1;-1;\n");
+        let graph = AdjacencyGraph::new(&map, adjacencies, HashSet::from([4]));
+
+        assert_eq!(graph.neighbours(1), vec![2, 4]);
+        assert_eq!(graph.neighbours(3), vec![2]);
+        assert!(graph.is_coastal(1));
+        assert!(!graph.is_coastal(3));
+        assert_eq!(graph.shortest_path(1, 3), Some(vec![1, 2, 3]));
+        assert_eq!(graph.connected_components(|id| id != 2).len(), 2);
+        assert_eq!(graph.bordering(&HashSet::from([3])), BTreeSet::from([2]));
+    }
+}
@@ -1,12 +1,18 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::PathBuf;
 
-use jomini::{JominiDeserialize, TextTape, Windows1252Encoding};
-use jomini::text::ValueReader;
+use jomini::common::Date;
+use jomini::{TextTape, Windows1252Encoding};
+use jomini::text::{Operator, ValueReader};
 use serde::Serialize;
 
 use crate::ideas::IdeaSet;
 use crate::localisation::parse_all_localisations;
+use crate::provincemap::{Colour, read_colour};
+use crate::snapshot::Snapshot;
+use crate::triggers::{compare, Condition, parse_condition};
+use crate::utils::{gather, read_strings, start_date};
 
 #[derive(Debug, Serialize, Default)]
 pub struct Country {
@@ -18,38 +24,293 @@ pub struct Country {
     pub adjective: String,
     pub ideas: IdeaSet,
     pub history: CountryHistory,
+    #[serde(skip)]
+    pub timeline: CountryTimeline,
+    #[serde(skip)]
+    pub data: CountryData,
     pub end_game_tag: bool,
 }
 
-#[derive(Debug, Serialize, JominiDeserialize, Default)]
+// The country as it is at one point in time, see CountryTimeline for the dated changes
+#[derive(Clone, Debug, Serialize, Default)]
 pub struct CountryHistory {
-    // pub tag: String,
-    #[jomini(default)]
     pub setup_vision: bool,
-    #[jomini(default)]
     pub government: String,
-    #[jomini(alias = "add_government_reform", duplicated)]
     pub government_reforms: Vec<String>,
-    #[jomini(default)]
     pub government_rank: usize,
-    #[jomini(default)]
     pub primary_culture: String,
-    #[jomini(alias = "add_accepted_culture", duplicated)]
     pub accepted_cultures: Vec<String>,
-    #[jomini(default)]
     pub religion: String,
-    #[jomini(default)]
     pub technology_group: String,
-    #[jomini(default)]
     pub capital: usize,
-    #[jomini(default)]
     pub fixed_capital: usize,
-    #[jomini(alias = "historical_rival", duplicated)]
     pub historical_rivals: Vec<String>,
-    #[jomini(alias = "historical_friend", duplicated)]
     pub historical_friends: Vec<String>,
 }
 
+// Anbennar gives every ruler one of these to mark their race
+pub const RACE_PERSONALITIES: [&str; 20] = [
+    "human_personality", "elf_personality", "half_elf_personality", "dwarf_personality",
+    "halfling_personality", "gnome_personality", "half_orc_personality", "orc_personality",
+    "goblin_personality", "kobold_personality", "ogre_personality", "troll_personality",
+    "harimari_personality", "gnoll_personality", "centaur_personality", "harpy_personality",
+    "hobgoblin_personality", "lizardfolk_personality", "ruinborn_personality", "mechanim_personality",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RulerKind {
+    Monarch,
+    Heir,
+    Queen,
+}
+
+impl RulerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RulerKind::Monarch => "Monarch",
+            RulerKind::Heir => "Heir",
+            RulerKind::Queen => "Consort",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ruler {
+    pub kind: RulerKind,
+    // the date of the history block the ruler was set in
+    pub date: Date,
+    pub name: String,
+    pub dynasty: Option<String>,
+    pub birth_date: Option<Date>,
+    pub adm: u8,
+    pub dip: u8,
+    pub mil: u8,
+    pub female: bool,
+    pub regent: bool,
+    pub culture: Option<String>,
+    pub religion: Option<String>,
+    pub personalities: Vec<String>,
+}
+
+impl Ruler {
+    fn parse(kind: RulerKind, date: Date, value: &ValueReader<Windows1252Encoding>) -> Option<Ruler> {
+        let block = value.read_object().ok()?;
+        let mut ruler = Ruler {
+            kind,
+            date,
+            name: String::new(),
+            dynasty: None,
+            birth_date: None,
+            adm: 0,
+            dip: 0,
+            mil: 0,
+            female: false,
+            regent: false,
+            culture: None,
+            religion: None,
+            personalities: vec![],
+        };
+        for (key, _op, value) in block.fields() {
+            let Ok(scalar) = value.read_string() else { continue };
+            match key.read_str().as_ref() {
+                "name" => ruler.name = scalar,
+                "dynasty" => ruler.dynasty = Some(scalar),
+                "birth_date" => ruler.birth_date = Date::parse(scalar).ok(),
+                "adm" => ruler.adm = scalar.parse().unwrap_or(0),
+                "dip" => ruler.dip = scalar.parse().unwrap_or(0),
+                "mil" => ruler.mil = scalar.parse().unwrap_or(0),
+                "female" => ruler.female = scalar == "yes",
+                "regency" => ruler.regent = scalar == "yes",
+                "culture" => ruler.culture = Some(scalar),
+                "religion" => ruler.religion = Some(scalar),
+                _ => {}
+            }
+        }
+        Some(ruler)
+    }
+
+    pub fn race(&self) -> Option<&str> {
+        self.personalities.iter()
+            .find(|p| RACE_PERSONALITIES.contains(&p.as_str()))
+            .map(|p| p.trim_end_matches("_personality"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Leader {
+    pub date: Date,
+    pub name: String,
+    // general, admiral, explorer or conquistador
+    pub leader_type: String,
+    pub fire: u8,
+    pub shock: u8,
+    pub manuever: u8,
+    pub siege: u8,
+    pub death_date: Option<Date>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountryHistoryEntry {
+    pub date: Date,
+    // key = value pairs in file order, applied with CountryHistory::apply
+    pub changes: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CountryTimeline {
+    // Everything outside of a dated block
+    pub base: CountryHistory,
+    // Sorted by date, blocks with the same date stay in file order
+    pub entries: Vec<CountryHistoryEntry>,
+    pub rulers: Vec<Ruler>,
+    pub leaders: Vec<Leader>,
+}
+
+impl CountryTimeline {
+    pub fn state_at(&self, date: Date) -> CountryHistory {
+        let mut state = self.base.clone();
+        for entry in self.entries.iter().take_while(|e| e.date <= date) {
+            for (key, value) in entry.changes.iter() {
+                state.apply(key, value);
+            }
+        }
+        state
+    }
+
+    // The last ruler of that kind set on or before the date, an heir stops being one as soon as
+    // a later monarch takes the throne
+    pub fn ruler_at(&self, kind: RulerKind, date: Date) -> Option<&Ruler> {
+        let ruler = self.rulers.iter()
+            .filter(|r| r.kind == kind && r.date <= date)
+            .last()?;
+        let succeeded = kind == RulerKind::Heir && self.rulers.iter()
+            .any(|r| r.kind == RulerKind::Monarch && r.date > ruler.date && r.date <= date);
+        if succeeded { None } else { Some(ruler) }
+    }
+
+    pub fn monarchs(&self) -> Vec<&Ruler> {
+        self.rulers.iter().filter(|r| r.kind == RulerKind::Monarch).collect()
+    }
+}
+
+impl CountryHistory {
+    // Answers the trigger leaves that only depend on the history, None for everything else
+    pub fn leaf<'a>(&'a self, tag: &'a str, culture_group: Option<&'a String>) -> impl Fn(&str, Option<Operator>, &str) -> Option<bool> + 'a {
+        move |key: &str, op: Option<Operator>, value: &str| -> Option<bool> {
+            // government_rank = 2 means at least a kingdom
+            if key == "government_rank" {
+                return Some(compare(self.government_rank, op, value.parse().ok()?));
+            }
+            if op.is_some() {
+                return None;
+            }
+            match key {
+                "tag" => Some(value == tag),
+                "primary_culture" => Some(value == self.primary_culture),
+                "culture_group" => culture_group.map(|group| value == group),
+                "religion" => Some(value == self.religion),
+                "technology_group" => Some(value == self.technology_group),
+                "government" => Some(value == self.government),
+                "has_reform" => Some(self.government_reforms.iter().any(|r| r == value)),
+                _ => None,
+            }
+        }
+    }
+
+    pub fn apply(&mut self, key: &str, value: &str) {
+        match key {
+            "setup_vision" => self.setup_vision = value == "yes",
+            "government" => self.government = value.to_string(),
+            "add_government_reform" => self.government_reforms.push(value.to_string()),
+            "government_rank" => self.government_rank = value.parse().unwrap_or(self.government_rank),
+            "primary_culture" => self.primary_culture = value.to_string(),
+            "add_accepted_culture" => self.accepted_cultures.push(value.to_string()),
+            "remove_accepted_culture" => self.accepted_cultures.retain(|c| c != value),
+            "religion" => self.religion = value.to_string(),
+            "technology_group" => self.technology_group = value.to_string(),
+            "capital" => self.capital = value.parse().unwrap_or(self.capital),
+            "fixed_capital" => self.fixed_capital = value.parse().unwrap_or(self.fixed_capital),
+            "historical_rival" => self.historical_rivals.push(value.to_string()),
+            "historical_friend" => self.historical_friends.push(value.to_string()),
+            _ => {}
+        }
+    }
+}
+
+pub fn parse_country_timeline(data: &[u8]) -> CountryTimeline {
+    let mut timeline = CountryTimeline::default();
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let key = key.read_str();
+        if let Ok(date) = Date::parse(key.as_ref()) {
+            if let Ok(block) = value.read_object() {
+                let mut entry = CountryHistoryEntry { date, changes: vec![] };
+                for (key, _op, value) in block.fields() {
+                    let key = key.read_str();
+                    match key.as_ref() {
+                        "monarch" => timeline.rulers.extend(Ruler::parse(RulerKind::Monarch, date, &value)),
+                        "heir" => timeline.rulers.extend(Ruler::parse(RulerKind::Heir, date, &value)),
+                        "queen" => timeline.rulers.extend(Ruler::parse(RulerKind::Queen, date, &value)),
+                        "leader" => timeline.leaders.extend(parse_leader(date, &value)),
+                        "add_ruler_personality" | "add_heir_personality" | "add_queen_personality" => {
+                            let kind = match key.as_ref() {
+                                "add_heir_personality" => RulerKind::Heir,
+                                "add_queen_personality" => RulerKind::Queen,
+                                _ => RulerKind::Monarch,
+                            };
+                            if let (Some(ruler), Ok(personality)) = (timeline.rulers.iter_mut().rev().find(|r| r.kind == kind), value.read_string()) {
+                                ruler.personalities.push(personality);
+                            }
+                        }
+                        key => {
+                            if let Ok(value) = value.read_string() {
+                                entry.changes.push((key.to_string(), value));
+                            }
+                        }
+                    }
+                }
+                timeline.entries.push(entry);
+            }
+        } else if let Ok(value) = value.read_string() {
+            timeline.base.apply(key.as_ref(), value.as_str());
+        }
+    }
+    // stable, so same day entries keep their order
+    timeline.entries.sort_by_key(|e| e.date);
+    timeline.rulers.sort_by_key(|r| r.date);
+    timeline.leaders.sort_by_key(|l| l.date);
+    timeline
+}
+
+fn parse_leader(date: Date, value: &ValueReader<Windows1252Encoding>) -> Option<Leader> {
+    let block = value.read_object().ok()?;
+    let mut leader = Leader {
+        date,
+        name: String::new(),
+        leader_type: String::new(),
+        fire: 0,
+        shock: 0,
+        manuever: 0,
+        siege: 0,
+        death_date: None,
+    };
+    for (key, _op, value) in block.fields() {
+        let Ok(scalar) = value.read_string() else { continue };
+        match key.read_str().as_ref() {
+            "name" => leader.name = scalar,
+            "type" => leader.leader_type = scalar,
+            "fire" => leader.fire = scalar.parse().unwrap_or(0),
+            "shock" => leader.shock = scalar.parse().unwrap_or(0),
+            "manuever" => leader.manuever = scalar.parse().unwrap_or(0),
+            "siege" => leader.siege = scalar.parse().unwrap_or(0),
+            "death_date" => leader.death_date = Date::parse(scalar).ok(),
+            _ => {}
+        }
+    }
+    Some(leader)
+}
+
 // Returns (TAG, path)
 pub fn parse_country_tags() -> Vec<(String, String)> {
     let mut tags = Vec::new();
@@ -89,8 +350,7 @@ pub fn parse_history_for_tag(tag: String) -> Option<CountryHistory> {
                 let filename = String::from_utf8_lossy(filename.as_encoded_bytes());
                 if filename.starts_with(&tag) {
                     let data = fs::read(file.path()).expect("error reading file");
-                    let actual: CountryHistory = jomini::text::de::from_windows1252_slice(data.as_slice()).unwrap();
-                    return Option::from(actual);
+                    return Some(parse_country_timeline(data.as_slice()).state_at(start_date()));
                 }
             }
             _ => {}
@@ -99,34 +359,89 @@ pub fn parse_history_for_tag(tag: String) -> Option<CountryHistory> {
     None
 }
 
-pub fn parse_country_histories() -> HashMap<String, CountryHistory> {
-    let mut histories: HashMap<String, CountryHistory> = HashMap::new();
+pub fn parse_country_timelines() -> HashMap<String, CountryTimeline> {
+    let mut timelines: HashMap<String, CountryTimeline> = HashMap::new();
     let paths = fs::read_dir("./anbennar/history/countries").expect("Missing country history directory");
     for path in paths {
         match path {
             Ok(file) => {
                 let tag = String::from(file.file_name().to_str().unwrap().split('-').collect::<Vec<&str>>()[0].trim());
                 let data = fs::read(file.path()).expect("error reading file");
-                let history: CountryHistory = jomini::text::de::from_windows1252_slice(data.as_slice()).unwrap();
-                histories.insert(tag, history);
+                timelines.insert(tag, parse_country_timeline(data.as_slice()));
             }
             _ => {}
         }
     }
 
-    histories
+    timelines
+}
+
+// common/countries/<name>.txt
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CountryData {
+    pub colour: Option<Colour>,
+    pub graphical_culture: Option<String>,
+    pub historical_idea_groups: Vec<String>,
+    pub historical_units: Vec<String>,
+    // name -> chance, negative chances are for female rulers
+    pub monarch_names: Vec<(String, i64)>,
+    pub leader_names: Vec<String>,
+    pub ship_names: Vec<String>,
+    pub army_names: Vec<String>,
+    pub fleet_names: Vec<String>,
+}
+
+pub fn parse_country_data(data: &[u8]) -> CountryData {
+    let mut country = CountryData::default();
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        match key.read_str().as_ref() {
+            "color" => country.colour = read_colour(&value),
+            "graphical_culture" => country.graphical_culture = value.read_string().ok(),
+            "historical_idea_groups" => country.historical_idea_groups = read_strings(&value),
+            "historical_units" => country.historical_units = read_strings(&value),
+            "leader_names" => country.leader_names = read_strings(&value),
+            "ship_names" => country.ship_names = read_strings(&value),
+            "army_names" => country.army_names = read_strings(&value),
+            "fleet_names" => country.fleet_names = read_strings(&value),
+            "monarch_names" => {
+                if let Ok(names) = value.read_object() {
+                    for (name, _op, chance) in names.fields() {
+                        let chance = chance.read_scalar().ok().and_then(|c| c.to_i64().ok()).unwrap_or(0);
+                        country.monarch_names.push((name.read_string(), chance));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    country
+}
+
+// TAG -> the data from the file anb_countries.txt points to
+pub fn parse_all_country_data() -> HashMap<String, CountryData> {
+    let mut countries = HashMap::new();
+    for (tag, path) in parse_country_tags() {
+        let Ok(data) = fs::read(format!("./anbennar/common/{path}")) else { continue };
+        countries.insert(tag, parse_country_data(data.as_slice()));
+    }
+    countries
 }
 
 pub fn parse_countries() -> Vec<Country>{
     let mut country_map: HashMap<String, Country> = HashMap::new();
-    let mut histories = parse_country_histories();
+    let mut timelines = parse_country_timelines();
+    let mut data = parse_all_country_data();
     let localisations = parse_all_localisations();
     let end_game_tags = end_game_tags();
 
     for (tag, _path) in parse_country_tags() {
         let mut country = Country::default();
         country.tag = tag.clone();
-        country.history = histories.remove(&tag).unwrap_or_else(|| CountryHistory::default());
+        country.timeline = timelines.remove(&tag).unwrap_or_default();
+        country.history = country.timeline.state_at(start_date());
+        country.data = data.remove(&tag).unwrap_or_default();
 
         if let Some(localisation) = localisations.get(&tag) {
             country.name = localisation.to_string();
@@ -156,82 +471,74 @@ pub fn parse_countries() -> Vec<Country>{
     country_map.into_values().collect()
 }
 
+// Tags for which was_never_end_game_tag_trigger is false, only tag and was_tag matter so
+// there's no need for the histories
 pub fn end_game_tags() -> HashSet<String> {
-    let mut results = HashSet::new();
     let file = fs::read("./anbennar/common/scripted_triggers/00_scripted_triggers.txt");
     let data = file.expect("Missing 00_scripted_triggers.txt");
     let tape = TextTape::from_slice(&*data).unwrap();
     let reader = tape.windows1252_reader();
 
-    fn sift(mut tags: HashSet<String>, obj: ValueReader<Windows1252Encoding>) -> HashSet<String> {
-        if let Ok(inner) = obj.read_object() {
-            for (key, _op, value) in inner.fields() {
-                if key.read_str() == "tag" || key.read_str() == "was_tag" {
-                    tags.insert(value.read_string().expect("could not parse as string"));
-                } else {
-                    tags = sift(tags.clone(), value);
-                }
-            }
-        }
-        tags
-    }
-
-    for (key, _op, value) in reader.fields() {
-        if key.read_str() == "was_never_end_game_tag_trigger" {
-            results = sift(results.clone(), value);
-            break;
-        }
-    }
-
-    results
+    let Some(trigger) = reader.fields()
+        .find(|(key, _op, _value)| key.read_str() == "was_never_end_game_tag_trigger")
+        .map(|(_key, _op, value)| parse_condition(&value)) else { return HashSet::new() };
+    let snapshot = Snapshot::default();
+    parse_country_tags()
+        .into_iter()
+        .map(|(tag, _path)| tag)
+        .filter(|tag| snapshot.evaluate(tag, &trigger) == Some(false))
+        .collect()
 }
 
-pub fn formable_tags() -> HashSet<String> {
-    let mut tags = HashSet::new();
-    let search_paths = vec!["./anbennar/decisions", "./anbennar/events"];
-
-    fn sift(mut tags: HashSet<String>, obj: ValueReader<Windows1252Encoding>) -> HashSet<String> {
-        if let Ok(inner) = obj.read_object() {
-            for (key, _op, value) in inner.fields() {
-                let key = key.read_str();
+// A decision that ends in change_tag
+#[derive(Debug, Clone)]
+pub struct Formable {
+    pub target: String,
+    pub potential: Condition,
+}
 
-                if key == "potential" || key == "provinces_to_highlight" || key == "allow" {
-                    continue;
-                }
-                if key == "change_tag" {
-                    tags.insert(value.read_string().expect("could not parse as string"));
-                    return tags
-                } else {
-                    tags = sift(tags.clone(), value);
-                }
-            }
+fn find_change_tag(value: &ValueReader<Windows1252Encoding>) -> Option<String> {
+    let inner = value.read_object().ok()?;
+    for (key, _op, value) in inner.fields() {
+        if key.read_str() == "change_tag" {
+            return value.read_string().ok();
+        }
+        if let Some(tag) = find_change_tag(&value) {
+            return Some(tag);
         }
-        tags
     }
+    None
+}
 
-    for path in search_paths {
-        let paths = fs::read_dir(path).expect(format!("Missing {path} directory").as_str());
-        for path in paths {
-            match path {
-                Ok(file) => {
-                    let file = fs::read(file.path()).expect("error reading file");
-                    let tape = TextTape::from_slice(file.as_slice()).unwrap();
-                    let reader = tape.windows1252_reader();
-                    for (_key, _op, value) in reader.fields() {
-                        if let Ok(country_decisions) = value.read_object() {
-                            for (_key, _op, value) in country_decisions.fields() {
-                                let parsed = sift(HashSet::new(), value);
-                                tags.extend(parsed);
-                            }
-                        }
+pub fn parse_formables() -> Vec<Formable> {
+    let mut formables = vec![];
+    let mut files: Vec<PathBuf> = vec![];
+    gather("./anbennar/decisions".to_string(), &mut files);
+    files.sort();
+    for file in files {
+        let data = fs::read(file.as_path()).expect("error reading file");
+        let tape = TextTape::from_slice(data.as_slice()).unwrap();
+        let reader = tape.windows1252_reader();
+        for (_key, _op, value) in reader.fields() {
+            let Ok(country_decisions) = value.read_object() else { continue };
+            for (_decision, _op, value) in country_decisions.fields() {
+                let Ok(fields) = value.read_object() else { continue };
+                let mut potential = Condition::default();
+                let mut target = None;
+                for (key, _op, value) in fields.fields() {
+                    match key.read_str().as_ref() {
+                        "potential" => potential = parse_condition(&value),
+                        "effect" => target = find_change_tag(&value),
+                        _ => {}
                     }
-                },
-                _ => {}
+                }
+                if let Some(target) = target {
+                    formables.push(Formable{ target, potential });
+                }
             }
         }
     }
-
-    tags
+    formables
 }
 
 
@@ -245,23 +552,56 @@ mod tests {
     }
 
     #[test]
-    pub fn test_parse_country_histories() {
-        parse_country_histories();
+    pub fn test_parse_country_timelines() {
+        parse_country_timelines();
     }
 
     #[test]
-    pub fn test_parse_end_game_tags() {
-        let tags = end_game_tags();
-        assert!(tags.len() > 0);
-        assert!(tags.contains("Z01"));
+    pub fn test_parse_country_timeline() {
+        let timeline = parse_country_timeline(b"government = monarchy primary_culture = lorentish add_accepted_culture = derannic
+            1444.1.1 = { monarch = { name = \"Rean\" dynasty = \"Siloriel\" adm = 3 dip = 2 mil = 1 } add_ruler_personality = human_personality }
+            1420.1.1 = { primary_culture = high_lorentish remove_accepted_culture = derannic }
+            1500.1.1 = { religion = corinite heir = { name = \"Ioriel\" adm = 1 dip = 1 mil = 1 } }");
+        let state = timeline.state_at(start_date());
+        assert_eq!(state.government, "monarchy");
+        assert_eq!(state.primary_culture, "high_lorentish");
+        assert!(state.accepted_cultures.is_empty());
+        assert_eq!(state.religion, "");
+        let monarch = timeline.ruler_at(RulerKind::Monarch, start_date()).unwrap();
+        assert_eq!(monarch.name, "Rean");
+        assert_eq!(monarch.dynasty.as_deref(), Some("Siloriel"));
+        assert_eq!((monarch.adm, monarch.dip, monarch.mil), (3, 2, 1));
+        assert_eq!(monarch.race(), Some("human"));
+        assert!(timeline.ruler_at(RulerKind::Heir, start_date()).is_none());
+
+        let timeline = parse_country_timeline(b"1400.1.1 = { monarch = { name = \"Adra\" } heir = { name = \"Rean\" } }
+            1430.1.1 = { monarch = { name = \"Rean\" } }");
+        assert_eq!(timeline.ruler_at(RulerKind::Heir, Date::from_ymd(1420, 1, 1)).unwrap().name, "Rean");
+        assert!(timeline.ruler_at(RulerKind::Heir, start_date()).is_none());
+        assert_eq!(timeline.ruler_at(RulerKind::Monarch, start_date()).unwrap().name, "Rean");
+    }
+
+    #[test]
+    pub fn test_parse_country_data() {
+        let data = parse_country_data(b"graphical_culture = westerngfx
+            color = { 100 50 25 }
+            historical_idea_groups = { economic_ideas offensive_ideas }
+            monarch_names = { \"Rean #1\" = 10 \"Adra #0\" = -1 }
+            leader_names = { Silmuna \"sil Lorent\" }
+            ship_names = { Lorentis }");
+        assert_eq!(data.colour, Some([100, 50, 25]));
+        assert_eq!(data.graphical_culture.as_deref(), Some("westerngfx"));
+        assert_eq!(data.historical_idea_groups, vec!["economic_ideas", "offensive_ideas"]);
+        assert_eq!(data.monarch_names, vec![("Rean #1".to_string(), 10), ("Adra #0".to_string(), -1)]);
+        assert_eq!(data.leader_names, vec!["Silmuna", "sil Lorent"]);
+        assert!(data.army_names.is_empty());
     }
 
     #[test]
-    pub fn test_parse_formable_tags() {
-        let tags = formable_tags();
+    pub fn test_parse_end_game_tags() {
+        let tags = end_game_tags();
         assert!(tags.len() > 0);
-        assert!(tags.contains("Z35")); // from decisions
-        assert!(tags.contains("Z01")); // from events
+        assert!(tags.contains("Z01"));
     }
 
     #[test]
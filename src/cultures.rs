@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use jomini::TextTape;
+
+use crate::countries::{CountryTimeline, RulerKind};
+use crate::utils::{gather, read_modifiers, read_strings, start_date};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Culture {
+    pub id: String,
+    pub group: String,
+    // the tag that gets released for the culture
+    pub primary: Option<String>,
+    pub male_names: Vec<String>,
+    pub female_names: Vec<String>,
+    pub dynasty_names: Vec<String>,
+    pub country_modifiers: BTreeMap<String, Vec<u8>>,
+    pub province_modifiers: BTreeMap<String, Vec<u8>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CultureGroup {
+    pub id: String,
+    pub graphical_culture: Option<String>,
+    pub second_graphical_culture: Option<String>,
+    pub male_names: Vec<String>,
+    pub female_names: Vec<String>,
+    pub dynasty_names: Vec<String>,
+    pub country_modifiers: BTreeMap<String, Vec<u8>>,
+    pub province_modifiers: BTreeMap<String, Vec<u8>>,
+    pub cultures: Vec<Culture>,
+    // see assign_races
+    pub race: Option<String>,
+}
+
+pub fn parse_culture_file(data: &[u8]) -> Vec<CultureGroup> {
+    let mut groups = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (group_id, _op, value) in reader.fields() {
+        let mut group = CultureGroup { id: group_id.read_string(), ..Default::default() };
+        let Ok(value) = value.read_object() else { continue };
+        for (key, _op, value) in value.fields() {
+            match key.read_str().as_ref() {
+                "graphical_culture" => group.graphical_culture = value.read_string().ok(),
+                "second_graphical_culture" => group.second_graphical_culture = value.read_string().ok(),
+                "male_names" => group.male_names = read_strings(&value),
+                "female_names" => group.female_names = read_strings(&value),
+                "dynasty_names" => group.dynasty_names = read_strings(&value),
+                "country" => group.country_modifiers = read_modifiers(&value),
+                "province" => group.province_modifiers = read_modifiers(&value),
+                culture_id => {
+                    let Ok(fields) = value.read_object() else { continue };
+                    let mut culture = Culture {
+                        id: culture_id.to_string(),
+                        group: group.id.clone(),
+                        ..Default::default()
+                    };
+                    for (key, _op, value) in fields.fields() {
+                        match key.read_str().as_ref() {
+                            "primary" => culture.primary = value.read_string().ok(),
+                            "male_names" => culture.male_names = read_strings(&value),
+                            "female_names" => culture.female_names = read_strings(&value),
+                            "dynasty_names" => culture.dynasty_names = read_strings(&value),
+                            "country" => culture.country_modifiers = read_modifiers(&value),
+                            "province" => culture.province_modifiers = read_modifiers(&value),
+                            _ => {}
+                        }
+                    }
+                    group.cultures.push(culture);
+                }
+            }
+        }
+        groups.push(group);
+    }
+    groups
+}
+
+pub fn parse_cultures() -> Vec<CultureGroup> {
+    let mut groups = vec![];
+    let mut files: Vec<PathBuf> = vec![];
+    gather("./anbennar/common/cultures".to_string(), &mut files);
+    files.sort();
+
+    for file in files {
+        let data = fs::read(file.as_path()).expect("error reading file");
+        groups.extend(parse_culture_file(data.as_slice()));
+    }
+    groups
+}
+
+// Returns culture -> culture group
+pub fn parse_culture_groups() -> BTreeMap<String, String> {
+    parse_cultures()
+        .into_iter()
+        .flat_map(|group| group.cultures.into_iter())
+        .map(|culture| (culture.id, culture.group))
+        .collect()
+}
+
+// A culture group assign_races couldn't give a race
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnassignedRace {
+    // none of its starting monarchs has a racial personality
+    NoVotes(String),
+    // the races that share the most monarchs
+    Tied(String, Vec<String>),
+}
+
+// Culture groups don't name their race, so every group takes the race most of its starting
+// monarchs have through their racial personality. Groups without votes or with a tie are
+// left without one and returned
+pub fn assign_races(groups: &mut [CultureGroup], timelines: &HashMap<String, CountryTimeline>) -> Vec<UnassignedRace> {
+    let culture_groups = groups.iter()
+        .flat_map(|g| g.cultures.iter())
+        .map(|c| (c.id.clone(), c.group.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut votes: HashMap<String, BTreeMap<String, usize>> = HashMap::new();
+    for timeline in timelines.values() {
+        let state = timeline.state_at(start_date());
+        let Some(ruler) = timeline.ruler_at(RulerKind::Monarch, start_date()) else { continue };
+        let culture = ruler.culture.as_ref().unwrap_or(&state.primary_culture);
+        if let (Some(group), Some(race)) = (culture_groups.get(culture), ruler.race()) {
+            *votes.entry(group.clone()).or_default().entry(race.to_string()).or_insert(0) += 1;
+        }
+    }
+    let mut unassigned = vec![];
+    for group in groups.iter_mut() {
+        let Some(votes) = votes.get(&group.id) else {
+            unassigned.push(UnassignedRace::NoVotes(group.id.clone()));
+            continue;
+        };
+        let most = votes.values().max().copied().unwrap_or(0);
+        let leaders = votes.iter().filter(|(_, count)| **count == most).map(|(race, _)| race.clone()).collect::<Vec<_>>();
+        match leaders.as_slice() {
+            [race] => group.race = Some(race.clone()),
+            _ => unassigned.push(UnassignedRace::Tied(group.id.clone(), leaders)),
+        }
+    }
+    unassigned
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::countries::parse_country_timeline;
+
+    use super::*;
+
+    #[test]
+    pub fn test_parse_culture_groups() {
+        let groups = parse_culture_groups();
+        assert!(groups.contains_key("high_lorentish"));
+    }
+
+    #[test]
+    pub fn test_parse_culture_file() {
+        let mut groups = parse_culture_file(b"lencori = {
+            graphical_culture = westerngfx
+            male_names = { Rean Caylen }
+            high_lorentish = { primary = A01 dynasty_names = { Siloriel } country = { prestige = 1 } }
+            low_lorentish = { }
+            dynasty_names = { Lorentis }
+        }");
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].graphical_culture.as_deref(), Some("westerngfx"));
+        assert_eq!(groups[0].male_names, vec!["Rean", "Caylen"]);
+        assert_eq!(groups[0].cultures.len(), 2);
+        assert_eq!(groups[0].cultures[0].primary.as_deref(), Some("A01"));
+        assert_eq!(groups[0].cultures[0].country_modifiers.get("prestige").unwrap(), b"1");
+
+        let timelines = HashMap::from([(
+            "A01".to_string(),
+            parse_country_timeline(b"primary_culture = high_lorentish 1440.1.1 = { monarch = { name = Rean } add_ruler_personality = human_personality }"),
+        )]);
+        assert!(assign_races(&mut groups, &timelines).is_empty());
+        assert_eq!(groups[0].race.as_deref(), Some("human"));
+    }
+
+    #[test]
+    pub fn test_assign_races_unassigned() {
+        let mut groups = parse_culture_file(b"lencori = { high_lorentish = { } low_lorentish = { } }
+            elven = { moon_elf = { } }
+            dwarven = { ruby_dwarf = { } }");
+        let timeline = |culture: &str, personality: &str| parse_country_timeline(format!(
+            "primary_culture = {culture} 1440.1.1 = {{ monarch = {{ name = Rean }} add_ruler_personality = {personality} }}"
+        ).as_bytes());
+        let timelines = HashMap::from([
+            ("A01".to_string(), timeline("high_lorentish", "human_personality")),
+            ("A02".to_string(), timeline("low_lorentish", "elf_personality")),
+            ("A03".to_string(), timeline("moon_elf", "elf_personality")),
+            ("A04".to_string(), timeline("ruby_dwarf", "careful_personality")),
+        ]);
+        let unassigned = assign_races(&mut groups, &timelines);
+        assert_eq!(unassigned, vec![
+            UnassignedRace::Tied("lencori".to_string(), vec!["elf".to_string(), "human".to_string()]),
+            UnassignedRace::NoVotes("dwarven".to_string()),
+        ]);
+        assert_eq!(groups[0].race, None);
+        assert_eq!(groups[1].race.as_deref(), Some("elf"));
+        assert_eq!(groups[2].race, None);
+    }
+}
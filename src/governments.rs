@@ -1,17 +1,20 @@
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
-use jomini::{Scalar, TextTape};
+use jomini::text::{Operator, ValueReader};
+use jomini::{Scalar, TextTape, Windows1252Encoding};
 
 use crate::localisation::parse_all_localisations;
-use crate::modifiers::get_modifier;
-use crate::utils::jsonify;
+use crate::triggers::{Condition, parse_condition};
+use crate::utils::{jsonify, read_bool, read_modifiers};
 
 #[derive(Debug, Default)]
 pub struct Government {
     pub id: String,
     pub basic_reform: String,
     pub color: Vec<u8>,
+    pub legacy_government: Option<String>,
+    pub exclusive_reforms: Vec<String>,
     pub reform_levels: BTreeMap<u8, ReformLevel>
 }
 
@@ -27,19 +30,115 @@ pub struct GovernmentReform {
     pub name: Option<String>,
     pub desc: Option<String>,
     pub icon: Option<String>,
-    pub potential: Option<String>,
-    // pub trigger
-    // pub conditional
+    pub potential: Option<Condition>,
+    pub trigger: Option<Condition>,
+    pub conditional: Vec<ReformConditional>,
     pub modifiers: BTreeMap<String, Vec<u8>>, // lifetime problems on scalar
-    // pub effect
-    // pub removed_effect
-    // pub custom_attributes
+    pub effect: Option<String>,
+    pub removed_effect: Option<String>,
+    pub custom_attributes: BTreeMap<String, bool>,
+    // every other yes/no switch, e.g. has_parliament or royal_marriage
+    pub attributes: BTreeMap<String, bool>,
 
     pub basic_reform: Option<bool>,
-    pub monarchy: Option<bool>
+    pub monarchy: Option<bool>,
+    pub republic: Option<bool>,
+    pub lock_level_when_selected: Option<bool>,
+    pub nation_designer_cost: Option<i64>
     // etc.
 }
 
+// Modifiers and switches that only apply while `allow` holds
+#[derive(Debug, Default)]
+pub struct ReformConditional {
+    pub allow: Condition,
+    pub modifiers: BTreeMap<String, Vec<u8>>,
+    pub attributes: BTreeMap<String, bool>,
+}
+
+fn read_attributes(value: &ValueReader<Windows1252Encoding>, attributes: &mut BTreeMap<String, bool>) {
+    if let Ok(value) = value.read_object() {
+        for (key, _op, value) in value.fields() {
+            if let Some(switch) = read_bool(&value) {
+                attributes.insert(key.read_string(), switch);
+            }
+        }
+    }
+}
+
+// yes/no keys a player sees on the reform, the rest are nation designer and AI bookkeeping
+const REFORM_ATTRIBUTES: [&str; 27] = [
+    "allow_banners", "allow_force_tributary", "allow_migration", "allow_vassal_alliance", "allow_vassal_war",
+    "boost_income", "can_form_trade_league", "can_use_trade_post", "claim_states", "dictatorship",
+    "foreign_slave_rulers", "free_city", "has_harem", "has_meritocracy", "has_parliament", "has_pasha",
+    "has_term_election", "heir", "heirs_can_be_generals", "is_elective", "is_trading_city", "monastic",
+    "native_mechanic", "queen", "religion", "royal_marriage", "rulers_can_be_generals",
+];
+
+fn parse_conditional(value: &ValueReader<Windows1252Encoding>) -> ReformConditional {
+    let mut conditional = ReformConditional::default();
+    if let Ok(fields) = value.read_object() {
+        for (key, _op, value) in fields.fields() {
+            match key.read_str().as_ref() {
+                "allow" => conditional.allow = parse_condition(&value),
+                "modifiers" => conditional.modifiers.extend(read_modifiers(&value)),
+                "custom_attributes" => read_attributes(&value, &mut conditional.attributes),
+                key if REFORM_ATTRIBUTES.contains(&key) => {
+                    if let Some(switch) = read_bool(&value) {
+                        conditional.attributes.insert(key.to_string(), switch);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    conditional
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Availability {
+    // potential is false, the reform doesn't show up at all
+    Hidden,
+    // shows up, but the trigger isn't known to pass
+    Potential,
+    Selectable,
+}
+
+impl GovernmentReform {
+    // A leaf the evaluator can't answer may go either way, so it can make a reform show up but
+    // never makes it selectable
+    pub fn availability<F>(&self, leaf: &F) -> Availability
+    where
+        F: Fn(&str, Option<Operator>, &str) -> Option<bool>,
+    {
+        let potential = self.potential.as_ref().map_or(Some(true), |c| c.evaluate(leaf));
+        if potential == Some(false) {
+            return Availability::Hidden;
+        }
+        let trigger = self.trigger.as_ref().map_or(Some(true), |c| c.evaluate(leaf));
+        if potential == Some(true) && trigger == Some(true) {
+            Availability::Selectable
+        } else {
+            Availability::Potential
+        }
+    }
+}
+
+// Tier -> the government's reforms in that tier with how available each one is
+pub fn reform_availability<F>(government: &Government, reforms: &HashMap<String, GovernmentReform>, leaf: &F) -> BTreeMap<u8, Vec<(String, Availability)>>
+where
+    F: Fn(&str, Option<Operator>, &str) -> Option<bool>,
+{
+    government.reform_levels.iter()
+        .map(|(tier, level)| {
+            let available = level.reforms.iter()
+                .filter_map(|id| reforms.get(id).map(|reform| (id.clone(), reform.availability(leaf))))
+                .collect();
+            (*tier, available)
+        })
+        .collect()
+}
+
 pub fn parse_government(data: &[u8]) -> Vec<Government> {
     let mut governments = vec![];
     let tape = TextTape::from_slice(data).unwrap();
@@ -80,10 +179,25 @@ pub fn parse_government(data: &[u8]) -> Vec<Government> {
                             }
                         }
                     }
-                    "basic_reform" => {}
-                    "legacy_government" => {}
-                    "exclusive_reforms" => {}
-                    "color" => {}
+                    "basic_reform" => {
+                        government.basic_reform = value.read_string().unwrap_or_default();
+                    }
+                    "legacy_government" => {
+                        government.legacy_government = value.read_string().ok();
+                    }
+                    "exclusive_reforms" => {
+                        if let Ok(v) = value.read_array() {
+                            government.exclusive_reforms = v.values().filter_map(|v| v.read_string().ok()).collect();
+                        }
+                    }
+                    "color" => {
+                        if let Ok(v) = value.read_array() {
+                            government.color = v.values()
+                                .filter_map(|v| v.read_scalar().ok().and_then(|s| s.to_u64().ok()))
+                                .map(|c| c as u8)
+                                .collect();
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -140,20 +254,27 @@ pub fn parse_government_reform_file(data: &[u8], localisations: Option<&HashMap<
                                     reform.icon = Some(value.unwrap());
                                 }
                             }
-                            "modifiers" => {
-                                let value = value.read_object();
-                                if value.is_ok() {
-                                    for (key, _op, value) in value.unwrap().fields() {
-                                        let modifier = get_modifier(&key.read_string(), );
-                                        if modifier.is_some() {
-                                            reform.modifiers.insert(key.read_string(), value.read_scalar().unwrap().as_bytes().to_vec());
-                                        }
-                                    }
-                                }
+                            "modifiers" => reform.modifiers.extend(read_modifiers(&value)),
+                            "potential" => reform.potential = Some(parse_condition(&value)),
+                            "trigger" => reform.trigger = Some(parse_condition(&value)),
+                            "conditional" => reform.conditional.push(parse_conditional(&value)),
+                            "effect" => reform.effect = Some(jsonify(value)),
+                            "removed_effect" => reform.removed_effect = Some(jsonify(value)),
+                            "custom_attributes" => read_attributes(&value, &mut reform.custom_attributes),
+                            "basic_reform" => reform.basic_reform = read_bool(&value),
+                            "monarchy" => reform.monarchy = read_bool(&value),
+                            "republic" => reform.republic = read_bool(&value),
+                            "lock_level_when_selected" => reform.lock_level_when_selected = read_bool(&value),
+                            "nation_designer_cost" => {
+                                reform.nation_designer_cost = value.read_scalar().ok().and_then(|s| s.to_i64().ok())
                             }
-                            "potential" => {
-                                reform.potential = Some(jsonify(value))
+                            key if REFORM_ATTRIBUTES.contains(&key) => {
+                                if let Some(switch) = read_bool(&value) {
+                                    reform.attributes.insert(key.to_string(), switch);
+                                }
                             }
+                            "valid_for_nation_designer" | "valid_for_new_country" | "allow_normal_conversion"
+                            | "fixed_rank" | "ai" | "legacy_equivalent" => {}
                             _ => {}
                         }
                     }
@@ -187,6 +308,8 @@ pub fn parse_government_reforms(localisations: Option<&HashMap<String, String>>)
 mod tests {
     use std::fs;
 
+    use crate::countries::CountryHistory;
+
     use super::*;
 
     #[test]
@@ -223,4 +346,87 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_parse_full_reform() {
+        let reforms = parse_government_reform_file(b"elective_monarchy = {
+            icon = crown
+            monarchy = yes
+            lock_level_when_selected = yes
+            nation_designer_cost = 15
+            has_parliament = yes
+            royal_marriage = no
+            valid_for_nation_designer = yes
+            fixed_rank = 2
+            potential = { NOT = { has_reform = theocracy_reform } }
+            trigger = { government = monarchy }
+            modifiers = { global_tax_modifier = 0.1 }
+            conditional = { allow = { has_dlc = \"Emperor\" } modifiers = { prestige = 1 } allow_banners = yes }
+            custom_attributes = { is_elective = yes }
+            effect = { set_country_flag = elected }
+        }
+        defaults_reform = { }", None);
+        assert_eq!(reforms.len(), 1);
+        let reform = &reforms[0];
+        assert_eq!(reform.monarchy, Some(true));
+        assert_eq!(reform.lock_level_when_selected, Some(true));
+        assert_eq!(reform.nation_designer_cost, Some(15));
+        assert_eq!(reform.attributes.get("has_parliament"), Some(&true));
+        assert_eq!(reform.attributes.get("royal_marriage"), Some(&false));
+        // nation designer flags aren't attributes, so they get no badge
+        assert!(!reform.attributes.contains_key("valid_for_nation_designer"));
+        assert_eq!(reform.attributes.len(), 2);
+        assert_eq!(reform.custom_attributes.get("is_elective"), Some(&true));
+        assert!(reform.trigger.is_some());
+        assert!(reform.effect.is_some());
+        assert_eq!(reform.conditional.len(), 1);
+        assert!(reform.conditional[0].modifiers.contains_key("prestige"));
+        assert_eq!(reform.conditional[0].attributes.get("allow_banners"), Some(&true));
+
+        let governments = parse_government(b"monarchy = {
+            basic_reform = monarchy_mechanic
+            legacy_government = despotic_monarchy
+            exclusive_reforms = { a b }
+            color = { 200 50 50 }
+        }");
+        assert_eq!(governments[0].basic_reform, "monarchy_mechanic");
+        assert_eq!(governments[0].legacy_government.as_deref(), Some("despotic_monarchy"));
+        assert_eq!(governments[0].exclusive_reforms, vec!["a", "b"]);
+        assert_eq!(governments[0].color, vec![200, 50, 50]);
+    }
+
+    #[test]
+    pub fn test_reform_availability() {
+        let governments = parse_government(b"monarchy = {
+            reform_levels = {
+                basic_monarchy_mechanic = { reforms = { feudal_monarchy elven_monarchy imperial_monarchy } }
+            }
+        }");
+        let reforms = parse_government_reform_file(b"feudal_monarchy = { }
+            elven_monarchy = { potential = { culture_group = elven } }
+            imperial_monarchy = { potential = { religion = regent_court } trigger = { government_rank = 3 } }", None)
+            .into_iter()
+            .map(|r| (r.id.clone(), r))
+            .collect::<HashMap<_, _>>();
+        let history = CountryHistory {
+            government: "monarchy".to_string(),
+            government_rank: 2,
+            religion: "regent_court".to_string(),
+            ..Default::default()
+        };
+        let culture_group = "lencori".to_string();
+        let leaf = history.leaf("A01", Some(&culture_group));
+        let available = reform_availability(&governments[0], &reforms, &leaf);
+        assert_eq!(available.get(&1).unwrap(), &vec![
+            ("feudal_monarchy".to_string(), Availability::Selectable),
+            ("elven_monarchy".to_string(), Availability::Hidden),
+            ("imperial_monarchy".to_string(), Availability::Potential),
+        ]);
+
+        let empire = CountryHistory { government_rank: 3, ..history.clone() };
+        let leaf = empire.leaf("A01", None);
+        assert_eq!(reforms.get("imperial_monarchy").unwrap().availability(&leaf), Availability::Selectable);
+        // without a culture group the evaluator can't rule the elven reform out
+        assert_eq!(reforms.get("elven_monarchy").unwrap().availability(&leaf), Availability::Potential);
+    }
+}
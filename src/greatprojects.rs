@@ -6,14 +6,15 @@ use jomini::text::ObjectReader;
 use serde::Deserialize;
 
 use crate::graphics::SpriteType;
+use crate::utils::jsonify;
 
 #[derive(Debug, Default)]
 pub struct GreatProject {
-    id: String,
-    start: Option<u64>,
-    starting_tier: Option<u64>,
-    project_type: String,
-    sprite: Option<SpriteType>,
+    pub id: String,
+    pub start: Option<u64>,
+    pub starting_tier: Option<u64>,
+    pub project_type: String,
+    pub sprite: Option<SpriteType>,
     // on_built
     // on_destroyed
     // can_be_moved
@@ -22,20 +23,20 @@ pub struct GreatProject {
     // can_upgrade_trigger
     // keep_trigger
     //tier_0: Option<Tier>, TODO: is this ever used?
-    tier_1: Option<Tier>,
-    tier_2: Option<Tier>,
-    tier_3: Option<Tier>,
+    pub tier_1: Option<Tier>,
+    pub tier_2: Option<Tier>,
+    pub tier_3: Option<Tier>,
 }
 
 #[derive(Debug, Default)]
 pub struct Tier {
-    upgrade_time: Option<u64>,
-    cost_to_upgrade: Option<u64>,
-    province_modifiers: BTreeMap<String, Vec<u8>>,
-    area_modifier: BTreeMap<String, Vec<u8>>,
-    region_modifier: BTreeMap<String, Vec<u8>>,
-    country_modifiers: BTreeMap<String, Vec<u8>>,
-    // TODO: on_upgraded
+    pub upgrade_time: Option<u64>,
+    pub cost_to_upgrade: Option<u64>,
+    pub province_modifiers: BTreeMap<String, Vec<u8>>,
+    pub area_modifier: BTreeMap<String, Vec<u8>>,
+    pub region_modifier: BTreeMap<String, Vec<u8>>,
+    pub country_modifiers: BTreeMap<String, Vec<u8>>,
+    pub on_upgraded: Option<String>,
 }
 
 pub fn parse_great_projects(data: &[u8]) -> Vec<GreatProject> {
@@ -91,6 +92,10 @@ fn parse_tier(value: Result<ObjectReader<Windows1252Encoding>, DeserializeError>
     let mut tier = Tier::default();
     for (key, _op, value) in value.unwrap().fields() {
         let key = key.read_str();
+        if key == "on_upgraded" {
+            tier.on_upgraded = Some(jsonify(value));
+            continue;
+        }
         if let Ok(value) = value.read_object() {
             match key.as_ref() {
                 "upgrade_time" => {
@@ -157,8 +162,38 @@ mod tests {
     #[test]
     pub fn test_great_projects() {
         let projects = parse_all_great_projects();
+        assert!(!projects.is_empty());
         for p in projects {
-            // println!("{:?}", p);
+            assert!(!p.id.is_empty());
         }
     }
+
+    #[test]
+    pub fn test_parse_great_projects() {
+        let projects = parse_great_projects(b"temple_of_corin = {
+            start = 67
+            type = monument
+            starting_tier = 1
+            tier_1 = {
+                upgrade_time = { months = 120 }
+                cost_to_upgrade = { factor = 1000 }
+                province_modifiers = { local_defensiveness = 0.1 }
+                country_modifiers = { prestige = 1 }
+                on_upgraded = { add_prestige = 10 }
+            }
+            tier_2 = { upgrade_time = { months = 240 } }
+        }");
+        assert_eq!(projects.len(), 1);
+        let project = &projects[0];
+        assert_eq!(project.start, Some(67));
+        assert_eq!(project.project_type, "monument");
+        assert_eq!(project.starting_tier, Some(1));
+        let tier = project.tier_1.as_ref().unwrap();
+        assert_eq!(tier.upgrade_time, Some(120));
+        assert_eq!(tier.cost_to_upgrade, Some(1000));
+        assert_eq!(tier.province_modifiers.len(), 1);
+        assert!(tier.on_upgraded.as_ref().unwrap().contains("add_prestige"));
+        assert_eq!(project.tier_2.as_ref().unwrap().upgrade_time, Some(240));
+        assert!(project.tier_3.is_none());
+    }
 }
\ No newline at end of file
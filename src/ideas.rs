@@ -1,47 +1,87 @@
 use std::collections::BTreeMap;
 use std::fs;
+use std::path::PathBuf;
 
-use jomini::{JominiDeserialize, TextTape};
+use jomini::{JominiDeserialize, TextTape, Windows1252Encoding};
+use jomini::text::ValueReader;
 use serde::Serialize;
 
+use crate::countries::parse_country_tags;
+use crate::modifiers::get_modifier;
+use crate::snapshot::Snapshot;
+use crate::triggers::{Condition, parse_condition};
+use crate::utils::{gather, jsonify};
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct CountryIdeaSets {
-    pub idea_sets: BTreeMap<String, IdeaSet>,
+    // In the order the game checks the triggers, the first matching set is used
+    pub idea_sets: Vec<IdeaSet>,
+    // TAG -> index into idea_sets
+    pub by_tag: BTreeMap<String, usize>,
+}
+
+impl CountryIdeaSets {
+    pub fn for_tag(&self, tag: &str) -> Option<&IdeaSet> {
+        self.by_tag.get(tag).map(|i| &self.idea_sets[*i])
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize)]
 pub struct IdeaSet {
-    pub tags: Vec<String>,
     pub name: String,
+    // ADM, DIP or MIL for idea groups, national ideas have none
+    pub category: Option<String>,
+    pub free: bool,
+    #[serde(skip)]
+    pub trigger: Option<Condition>,
     pub start: BTreeMap<String, String>,
     pub bonus: BTreeMap<String, String>,
     pub ideas: Vec<Idea>,
 }
 
+impl IdeaSet {
+    // Culture, religion and default ideas don't name any tags in their trigger
+    pub fn is_generic(&self) -> bool {
+        match &self.trigger {
+            Some(trigger) => trigger.tags().is_empty(),
+            None => true,
+        }
+    }
+}
+
 #[derive(Clone, JominiDeserialize, PartialEq, Debug, Default, Serialize)]
 pub struct Idea {
     pub name: String,
     #[jomini(default)]
     pub description: String,
     pub effects: BTreeMap<String, String>,
+    // yes/no entries that aren't modifiers, e.g. may_explore
+    #[jomini(default)]
+    pub switches: BTreeMap<String, String>,
+    // jsonified effect blocks
+    #[jomini(default)]
+    pub effect: Option<String>,
+    #[jomini(default)]
+    pub removed_effect: Option<String>,
 }
 
-pub fn parse_ideas() -> CountryIdeaSets {
-    let data = fs::read("./anbennar/common/ideas/anb_country_ideas.txt")
-        .expect("Ideas file not found");
-    let mut idea_sets = CountryIdeaSets{
-        idea_sets: Default::default()
-    };
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Policy {
+    pub category: String,
+    // the idea groups that have to be completed
+    pub idea_groups: Vec<String>,
+    pub allow: Option<Condition>,
+    pub idea: Idea,
+}
 
-    let tape = TextTape::from_slice(data.as_slice()).unwrap();
+fn parse_idea_file(data: &[u8]) -> Vec<IdeaSet> {
+    let mut idea_sets = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
     let reader = tape.windows1252_reader();
     for (key, _op, value) in reader.fields() {
         let mut set: IdeaSet = IdeaSet{
-            tags: vec![],
             name: key.read_string(),
-            start: Default::default(),
-            bonus: Default::default(),
-            ideas: vec![],
+            ..Default::default()
         };
         if let Ok(idea_group) = value.read_object() {
             for (key, _op, value) in idea_group.fields() {
@@ -59,36 +99,21 @@ pub fn parse_ideas() -> CountryIdeaSets {
                         }
                     }
                 } else if key == "free" {
-                    // pass
+                    set.free = value.read_str().is_ok_and(|v| v == "yes");
+                } else if key == "category" {
+                    set.category = value.read_string().ok();
                 } else if key == "trigger" {
-                    if let Ok(modifiers) = value.read_object() {
-                        for (key, _, value) in modifiers.fields() {
-                            let key = key.read_str();
-                            if key == "OR" {
-                                if let Ok(tags) = value.read_object() {
-                                    for (key, _op, value) in tags.fields() {
-                                        if key.read_string().to_lowercase() == "tag" {
-                                            set.tags.push(value.read_string().unwrap());
-                                        }
-                                    }
-                                }
-                            } else if key == "tag" {
-                                set.tags.push(value.read_string().unwrap());
-                            }
-                        }
-                    }
+                    set.trigger = Some(parse_condition(&value));
+                } else if key == "ai_will_do" || key == "important" {
+                    // pass
                 } else {
                     if let Ok(modifiers) = value.read_object() {
                         let mut idea = Idea{
                             name: key.to_string(),
-                            description: "".to_string(),
-                            effects: Default::default(),
+                            ..Default::default()
                         };
                         for (key, _op, value) in modifiers.fields() {
-                            // there can be effects in ideas - skipping those for now
-                            if let Ok(value) = value.read_string() {
-                                idea.effects.insert(key.read_string(), value);
-                            }
+                            parse_idea_entry(&mut idea, key.read_string(), value);
                         }
                         set.ideas.push(idea);
                     }
@@ -96,26 +121,218 @@ pub fn parse_ideas() -> CountryIdeaSets {
             }
         }
 
-        for tag in &set.tags {
-            idea_sets.idea_sets.insert(tag.to_string(), set.clone());
+        idea_sets.push(set);
+    }
+
+    idea_sets
+}
+
+fn parse_idea_entry(idea: &mut Idea, key: String, value: ValueReader<Windows1252Encoding>) {
+    match key.as_str() {
+        "effect" => idea.effect = Some(jsonify(value)),
+        "removed_effect" => idea.removed_effect = Some(jsonify(value)),
+        _ => {
+            if let Ok(value) = value.read_string() {
+                if get_modifier(&key).is_none() && (value == "yes" || value == "no") {
+                    idea.switches.insert(key, value);
+                } else {
+                    idea.effects.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+fn parse_all_idea_files() -> Vec<IdeaSet> {
+    let mut idea_sets = vec![];
+    let mut files: Vec<PathBuf> = vec![];
+    gather("./anbennar/common/ideas".to_string(), &mut files);
+    // the game reads the files in alphabetical order
+    files.sort();
+
+    for file in files {
+        let data = fs::read(file.as_path()).expect("Ideas file not found");
+        idea_sets.extend(parse_idea_file(data.as_slice()));
+    }
+    idea_sets
+}
+
+// Basic and custom idea groups, i.e. everything that is picked with a category
+pub fn parse_idea_groups() -> Vec<IdeaSet> {
+    parse_all_idea_files()
+        .into_iter()
+        .filter(|set| !set.free && set.category.is_some())
+        .collect()
+}
+
+pub fn parse_ideas() -> CountryIdeaSets {
+    parse_ideas_for(&Snapshot::at_start())
+}
+
+pub fn parse_ideas_for(snapshot: &Snapshot) -> CountryIdeaSets {
+    let mut idea_sets = CountryIdeaSets{
+        // basic idea groups are not free
+        idea_sets: parse_all_idea_files().into_iter().filter(|set| set.free).collect(),
+        by_tag: Default::default(),
+    };
+
+    for (tag, _path) in parse_country_tags() {
+        if let Some(i) = idea_set_for(&idea_sets.idea_sets, snapshot, &tag) {
+            idea_sets.by_tag.insert(tag, i);
         }
     }
 
     idea_sets
 }
 
+// The first set whose trigger doesn't fail. One the snapshot can't decide still wins, the sets
+// go from specific to generic, so the earlier one is the better guess over falling through to
+// culture or default ideas
+fn idea_set_for(sets: &[IdeaSet], snapshot: &Snapshot, tag: &str) -> Option<usize> {
+    let facts = snapshot.leaf(tag);
+    sets.iter().position(|set| match &set.trigger {
+        Some(trigger) => trigger.evaluate(&facts) != Some(false),
+        None => true,
+    })
+}
+
+fn parse_policy_file(data: &[u8]) -> Vec<Policy> {
+    let mut policies = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let mut policy = Policy::default();
+        policy.idea.name = key.read_string();
+        if let Ok(value) = value.read_object() {
+            for (key, _op, value) in value.fields() {
+                match key.read_str().as_ref() {
+                    "monarch_power" => {
+                        policy.category = value.read_string().unwrap_or_default();
+                    }
+                    "allow" => {
+                        let allow = parse_condition(&value);
+                        policy.idea_groups = allow_idea_groups(&allow);
+                        policy.allow = Some(allow);
+                    }
+                    "potential" | "ai_will_do" => {}
+                    key => {
+                        parse_idea_entry(&mut policy.idea, key.to_string(), value);
+                    }
+                }
+            }
+        }
+        policies.push(policy);
+    }
+    policies
+}
+
+fn allow_idea_groups(condition: &Condition) -> Vec<String> {
+    let mut groups = vec![];
+    match condition {
+        Condition::And(conditions) => {
+            for condition in conditions {
+                groups.extend(allow_idea_groups(condition));
+            }
+        }
+        Condition::Leaf { key, value, .. } if key == "full_idea_group" => {
+            groups.push(value.to_string());
+        }
+        _ => {}
+    }
+    groups
+}
+
+pub fn parse_policies() -> Vec<Policy> {
+    let mut policies = vec![];
+    let mut files: Vec<PathBuf> = vec![];
+    gather("./anbennar/common/policies".to_string(), &mut files);
+    files.sort();
+
+    for file in files {
+        let data = fs::read(file.as_path()).expect("error reading file");
+        policies.extend(parse_policy_file(data.as_slice()));
+    }
+    policies
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use crate::countries::CountryHistory;
+
     use super::*;
 
+    #[test]
+    pub fn test_idea_file_parse() {
+        let data = b"A01_ideas = { start = { discipline = 0.05 } bonus = { prestige = 1 } trigger = { tag = A01 } free = yes a = { prestige = 1 } }
+            lencori_ideas = { start = { discipline = 0.05 } trigger = { culture_group = lencori } free = yes }
+            aristocracy_ideas = { category = MIL a = { prestige = 1 may_sow_discontent = yes effect = { add_prestige = 10 } } ai_will_do = { factor = 1 } }";
+        let sets = parse_idea_file(data);
+        assert_eq!(sets.len(), 3);
+        assert!(!sets[0].is_generic());
+        assert_eq!(sets[0].ideas.len(), 1);
+        assert!(sets[1].is_generic());
+        assert!(!sets[2].free);
+        assert_eq!(sets[2].category.as_deref(), Some("MIL"));
+        assert_eq!(sets[2].ideas.len(), 1);
+        assert_eq!(sets[2].ideas[0].switches.get("may_sow_discontent").unwrap(), "yes");
+        assert!(sets[2].ideas[0].effect.is_some());
+    }
+
+    #[test]
+    pub fn test_idea_set_for() {
+        let sets = parse_idea_file(b"A01_ideas = { trigger = { tag = A01 has_country_flag = lorent_flag } free = yes }
+            lencori_ideas = { trigger = { culture_group = lencori } free = yes }
+            default_ideas = { free = yes }");
+        let lorentish = CountryHistory { primary_culture: "high_lorentish".to_string(), ..Default::default() };
+        let dwarven = CountryHistory { primary_culture: "ruby_dwarf".to_string(), ..Default::default() };
+        let snapshot = Snapshot::new(
+            HashMap::from([
+                ("A01".to_string(), lorentish.clone()),
+                ("A02".to_string(), lorentish),
+                ("B01".to_string(), dwarven),
+            ]),
+            BTreeMap::new(),
+            BTreeMap::from([
+                ("high_lorentish".to_string(), "lencori".to_string()),
+                ("ruby_dwarf".to_string(), "dwarven".to_string()),
+            ]),
+            HashMap::new(),
+        );
+        // the flag can't be known from history, A01 keeps its national ideas anyway
+        assert_eq!(idea_set_for(&sets, &snapshot, "A01"), Some(0));
+        assert_eq!(idea_set_for(&sets, &snapshot, "A02"), Some(1));
+        assert_eq!(idea_set_for(&sets, &snapshot, "B01"), Some(2));
+    }
+
+    #[test]
+    pub fn test_policy_file_parse() {
+        let data = b"the_combination_act = {
+            monarch_power = ADM
+            potential = { has_idea_group = aristocracy_ideas has_idea_group = plutocracy_ideas }
+            allow = { full_idea_group = aristocracy_ideas full_idea_group = plutocracy_ideas }
+            global_tax_modifier = 0.1
+            ai_will_do = { factor = 1 }
+        }";
+        let policies = parse_policy_file(data);
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies[0].category, "ADM");
+        assert_eq!(policies[0].idea_groups, vec!["aristocracy_ideas", "plutocracy_ideas"]);
+        assert_eq!(policies[0].idea.effects.get("global_tax_modifier").unwrap(), "0.1");
+    }
+
     #[test]
     pub fn test_idea_parse() {
         let actual = parse_ideas();
-        for (_, set) in actual.idea_sets.iter() {
+        for (i, set) in actual.idea_sets.iter().enumerate() {
             assert_ne!(set.name, "");
             assert_ne!(set.start.len(), 0);
-            assert_ne!(set.tags.len(), 0);
             assert_eq!(set.ideas.len(), 7);
+            if !set.is_generic() {
+                assert!(actual.by_tag.values().any(|j| *j == i), "{} applies to no country", set.name);
+            }
         }
+        assert_eq!(actual.for_tag("A01").unwrap().name, "A01_ideas");
     }
 }
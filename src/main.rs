@@ -6,25 +6,32 @@ use std::string::String;
 use std::time::Instant;
 use convert_case::{Case, Casing};
 use deunicode::deunicode;
-use jomini::{Scalar, TextTape};
+use jomini::Scalar;
+use jomini::common::PdsDate;
 use log::__private_api::loc;
 use reqwest::blocking::Client;
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use serde::de::Unexpected::Str;
 use serde_json::Value;
+use crate::adjacency::{AdjacencyGraph, parse_adjacency_graph};
 use crate::bundled_modifiers::parse_bundled_modifiers;
-use crate::countries::{Country, formable_tags};
-use crate::governments::{parse_government_reforms, parse_governments};
-use crate::ideas::parse_ideas;
+use crate::countries::{Country, CountryData, CountryTimeline, Formable, parse_all_country_data, parse_country_timelines, parse_formables, Ruler, RulerKind};
+use crate::cultures::{assign_races, CultureGroup, parse_cultures, UnassignedRace};
+use crate::governments::{Availability, Government, GovernmentReform, parse_government_reforms, parse_governments, reform_availability};
+use crate::greatprojects::{GreatProject, parse_all_great_projects, Tier};
+use crate::ideas::{CountryIdeaSets, Idea, parse_idea_groups, parse_ideas, parse_ideas_for, parse_policies};
 use crate::imagemagick::ImageMagick;
 use crate::localisation::{parse_all_localisations, parse_idea_localisations};
-use crate::map::{parse_continents, parse_map};
+use crate::map::{Area, parse_continents, parse_map, parse_map_with_geography, parse_map_with_report, Province, Region, SuperRegion};
 use crate::missions::{parse_missions, tags_with_missions};
-use crate::modifiers::{get_modifier, MODIFIERS};
-use crate::modifiers::ModifierNormal::{Negative, Positive};
-use crate::religions::{parse_religious_groups, Religion};
-use crate::utils::{get_git_changed_files, htmlify, gather, parse_all_icons};
+use crate::modifiers::{format_modifier, format_modifiers, get_modifier, FormattedModifier, ModifierSourceType, MODIFIERS, parse_modifier_uses};
+use crate::provincemap::{Colour, gradient_colour, hashed_colour, Image, parse_province_map};
+use crate::races::{parse_races, race_stem};
+use crate::religions::{count_adherents, parse_church_aspects, parse_fetishist_cults, parse_personal_deities, parse_religious_groups, parse_religious_reforms, Religion, ReligiousGroup};
+use crate::snapshot::Snapshot;
+use crate::trade::{parse_trade_goods, parse_trade_nodes};
+use crate::utils::{get_git_changed_files, htmlify, gather, parse_all_icons, start_date};
 
 mod localisation;
 mod ideas;
@@ -41,15 +48,30 @@ mod greatprojects;
 mod graphics;
 mod bundled_modifiers;
 mod decisions;
+mod triggers;
+mod cultures;
+mod provincemap;
+mod adjacency;
+mod trade;
+mod races;
+mod snapshot;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let api_url = env::var("API_URL").unwrap();
-    let bot_name = env::var("BOTNAME").unwrap();
-    let bot_pass = env::var("BOTPASS").unwrap();
+    // --dry-run prints every page instead of sending it, so no wiki credentials are needed
+    let dry_run = args.contains(&String::from("--dry-run"));
+    let credential = |name: &str| env::var(name).unwrap_or_else(|_| {
+        if dry_run { String::new() } else { panic!("Missing {name}") }
+    });
+    let api_url = credential("API_URL");
+    let bot_name = credential("BOTNAME");
+    let bot_pass = credential("BOTPASS");
 
     let mut mwclient = MediaWikiClient::new(api_url, bot_name, bot_pass);
-    mwclient.login();
+    mwclient.dry_run = dry_run;
+    if !dry_run {
+        mwclient.login();
+    }
 
     preprocess_modifiers();
 
@@ -62,14 +84,17 @@ fn main() {
     if args.contains(&String::from("--ideas")) {
         idea_pages(&mut mwclient);
     }
+    if args.contains(&String::from("--idea-groups")) {
+        idea_group_pages(&mut mwclient);
+    }
     if args.contains(&String::from("--countries")) {
         country_list_and_details(&mut mwclient);
     }
     if args.contains(&String::from("--flags")) {
         upload_flags(&mut mwclient);
     }
-    if args.contains(&String::from("--racial-modifiers")) {
-        racial_modifiers(&mut mwclient);
+    if args.contains(&String::from("--races")) || args.contains(&String::from("--racial-modifiers")) {
+        run_races(&mut mwclient);
     }
     if args.contains(&String::from("--governments")) {
         run_governments(&mut mwclient);
@@ -80,9 +105,31 @@ fn main() {
     if args.contains(&String::from("--religions")) {
         run_religions(&mut mwclient);
     }
+    if args.contains(&String::from("--validate-map")) {
+        let (_map, report) = parse_map_with_report();
+        report.print();
+        if report.is_empty() {
+            println!("No problems found in the map files");
+        }
+    }
     if args.contains(&String::from("--map")) {
         run_map(&mut mwclient)
     }
+    if args.contains(&String::from("--map-images")) {
+        run_map_images(&mut mwclient)
+    }
+    if args.contains(&String::from("--provinces")) {
+        run_provinces(&mut mwclient)
+    }
+    if args.contains(&String::from("--cultures")) {
+        run_cultures(&mut mwclient)
+    }
+    if args.contains(&String::from("--trade")) {
+        run_trade(&mut mwclient)
+    }
+    if args.contains(&String::from("--great-projects")) {
+        run_great_projects(&mut mwclient)
+    }
     if args.contains(&String::from("--missions")) {
         run_missions(&mut mwclient)
     }
@@ -107,6 +154,7 @@ struct MediaWikiClient {
     csrf_time: Instant,
     csrf_counter: u8,
     httpclient: Client,
+    dry_run: bool,
 }
 
 impl MediaWikiClient {
@@ -124,6 +172,7 @@ impl MediaWikiClient {
                 .cookie_store(true)
                 .default_headers(default_headers)
                 .build().unwrap(),
+            dry_run: false,
         }
     }
 
@@ -155,6 +204,10 @@ impl MediaWikiClient {
     }
 
     pub fn upload(&mut self, filename: String, path: &PathBuf) {
+        if self.dry_run {
+            println!("Would upload {} as {filename}", path.display());
+            return;
+        }
         let form = reqwest::blocking::multipart::Form::new()
             .text("action", "upload")
             .text("filename", filename)
@@ -171,6 +224,10 @@ impl MediaWikiClient {
         if title.trim() == "" {
             panic!("Not editing page without title")
         }
+        if self.dry_run {
+            println!("Would update {title}:\n{text}\n");
+            return;
+        }
         println!("Updating {title}");
         let csrf: String = self.csrf();
         let title = title.clone();
@@ -221,9 +278,11 @@ fn preprocess_modifiers() {
 
 fn idea_pages(client: &mut MediaWikiClient) {
     let mut country_idea_sets = parse_ideas();
-    let idea_localisations = parse_idea_localisations();
+    // culture and generic ideas aren't all localised in the powers & ideas file
+    let idea_localisations = parse_all_localisations();
+    let mut warnings = vec![];
 
-    for (_tag, set) in country_idea_sets.idea_sets.iter_mut() {
+    for set in country_idea_sets.idea_sets.iter_mut() {
         let set_name = idea_localisations.get(&set.name);
         if let Some(set_name) = set_name {
             if set_name == "" {
@@ -234,6 +293,10 @@ fn idea_pages(client: &mut MediaWikiClient) {
             page_body += format!("<!-- {set_name} -->\n").as_str();
             let mut counter = 1;
             for idea in set.start.iter() {
+                if get_modifier(idea.0).is_none() {
+                    warnings.push(format!("{}: {} = {}", set.name, idea.0, idea.1));
+                    continue;
+                }
                 let (desc, value) = modifiers::localise_strings(idea.0, idea.1);
                 page_body += format!("|tradition{}name={}\n", counter, desc).as_str();
                 page_body += format!("|tradition{}effect={}\n", counter, value).as_str();
@@ -242,6 +305,7 @@ fn idea_pages(client: &mut MediaWikiClient) {
 
             counter = 1;
             for idea in set.ideas.iter_mut() {
+                let source = format!("{}: {}", set.name, idea.name);
                 let name = idea_localisations.get(&idea.name);
                 let desc = idea_localisations.get((&format!("{}_desc", &idea.name)).into());
                 if let Some(name) = name {
@@ -255,27 +319,18 @@ fn idea_pages(client: &mut MediaWikiClient) {
                 page_body += format!("|idea{counter}name={name}\n", counter=counter, name=idea.name).as_str();
                 page_body += format!("|idea{counter}desc={desc}\n", counter=counter, desc=idea.description).as_str();
                 page_body += format!("|idea{counter}effect=").as_str();
-                let mut counter2 = 0;
-                for effect in idea.effects.iter_mut() {
-                    let (desc, value) = modifiers::localise_strings(effect.0, effect.1);
-                    if counter2 > 0 {
-                        page_body += &*"<br />".to_string();
-                    }
-                    page_body += format!("{{{{Modifier|type=bonus|value={value}|description={desc} }}}}", value=value, desc=desc).as_str();
-                    counter2 += 1;
-                }
+                page_body += idea_entries(idea, &idea_localisations, &source, &mut warnings).as_str();
                 page_body += &*"\n".to_string();
                 counter += 1;
             }
 
-            counter = 1;
             page_body += &*"|ambitioneffect=".to_string();
-            for effect in set.bonus.iter() {
-                let (desc, value) = modifiers::localise_strings(effect.0, effect.1);
-                page_body += format!("{{{{Modifier|type=bonus|value={value}|description={desc} }}}}", value=value, desc=desc).as_str();
-                counter += 1;
+            page_body += idea_effects(&set.bonus, &idea_localisations, &set.name, &mut warnings).as_str();
+            page_body += "}}\n\n<noinclude>[[Category:Country Ideas]]";
+            if set.is_generic() {
+                page_body += "[[Category:Generic Ideas]]";
             }
-            page_body += "}}\n\n<noinclude>[[Category:Country Ideas]]</noinclude>\n";
+            page_body += "</noinclude>\n";
             let normal_set_name = deunicode(set_name);
             if String::ne(set_name, &normal_set_name) {
                 client.redirect(set_name, &normal_set_name);
@@ -283,6 +338,137 @@ fn idea_pages(client: &mut MediaWikiClient) {
             client.add_edit_page(&normal_set_name, page_body);
         }
     }
+
+    if !warnings.is_empty() {
+        println!("Unknown modifiers in ideas:");
+        for warning in warnings {
+            println!("  {}", warning);
+        }
+    }
+}
+
+// Renders modifiers and yes/no switches, anything else ends up in `warnings`
+fn idea_effects(effects: &BTreeMap<String, String>, localisations: &HashMap<String, String>, source: &str, warnings: &mut Vec<String>) -> String {
+    let mut rendered = vec![];
+    for (key, value) in effects {
+        if let Some(formatted) = format_modifier(key, value.as_bytes()) {
+            rendered.push(formatted.to_template(false));
+        } else if value == "yes" || value == "no" {
+            let switch = FormattedModifier{
+                id: key.to_string(),
+                value: "".to_string(),
+                colour: if value == "yes" { "bonus" } else { "malus" },
+                description: match localisations.get(key) {
+                    Some(name) if !name.is_empty() => name.to_string(),
+                    _ => key.to_case(Case::Title),
+                },
+            };
+            rendered.push(switch.to_template(false));
+        } else {
+            warnings.push(format!("{source}: {key} = {value}"));
+        }
+    }
+    rendered.join("<br />")
+}
+
+fn idea_entries(idea: &Idea, localisations: &HashMap<String, String>, source: &str, warnings: &mut Vec<String>) -> String {
+    let mut rendered = vec![];
+    let effects = idea_effects(&idea.effects, localisations, source, warnings);
+    if !effects.is_empty() {
+        rendered.push(effects);
+    }
+    let switches = idea_effects(&idea.switches, localisations, source, warnings);
+    if !switches.is_empty() {
+        rendered.push(switches);
+    }
+    if let Some(effect) = &idea.effect {
+        if let Ok(json) = serde_json::from_str::<Value>(effect) {
+            rendered.push(format!("When taken:{}", htmlify(&json)));
+        }
+    }
+    if let Some(effect) = &idea.removed_effect {
+        if let Ok(json) = serde_json::from_str::<Value>(effect) {
+            rendered.push(format!("When removed:{}", htmlify(&json)));
+        }
+    }
+    rendered.join("<br />")
+}
+
+fn idea_group_pages(client: &mut MediaWikiClient) {
+    let localisations = parse_all_localisations();
+    let idea_groups = parse_idea_groups();
+    let policies = parse_policies();
+    let mut warnings = vec![];
+    let localise = |key: &String| -> String {
+        match localisations.get(key) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => key.to_case(Case::Title),
+        }
+    };
+
+    for group in idea_groups.iter() {
+        let group_name = deunicode(localise(&group.name).as_str());
+        let category = group.category.clone().unwrap_or_default();
+        let mut page_str = String::new();
+        page_str += format!("'''{group_name}''' is a {category} idea group.\n\n").as_str();
+        if let Some(trigger) = &group.trigger {
+            page_str += format!("Available to countries that fulfil:\n{}\n\n", htmlify(&trigger.to_json())).as_str();
+        }
+        page_str += "{| class=\"wikitable\"\n|-\n! Idea !! Effects !! Description\n";
+        for idea in group.ideas.iter() {
+            page_str += format!(
+                "|-\n| {} || {} || {}\n",
+                localise(&idea.name),
+                idea_entries(idea, &localisations, &group.name, &mut warnings),
+                localisations.get(&format!("{}_desc", idea.name)).cloned().unwrap_or_default()
+            ).as_str();
+        }
+        page_str += format!("|-\n| Ambition || {} ||\n", idea_effects(&group.bonus, &localisations, &group.name, &mut warnings)).as_str();
+        page_str += "|}\n";
+
+        let group_policies = policies.iter().filter(|p| p.idea_groups.contains(&group.name)).collect::<Vec<_>>();
+        if !group_policies.is_empty() {
+            page_str += "\n== Policies ==\n{| class=\"wikitable sortable\"\n|-\n! Policy !! Combined with !! Effects\n";
+            for policy in group_policies {
+                let other = policy.idea_groups.iter()
+                    .filter(|g| **g != group.name)
+                    .map(|g| format!("[[{}]]", deunicode(localise(g).as_str())))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                page_str += format!("|-\n| {} || {} || {}\n", localise(&policy.idea.name), other, idea_entries(&policy.idea, &localisations, &policy.idea.name, &mut warnings)).as_str();
+            }
+            page_str += "|}\n";
+        }
+        page_str += format!("\n<noinclude>[[Category:Idea Groups]][[Category:{category} Idea Groups]]</noinclude>\n").as_str();
+        client.add_edit_page(&group_name, page_str);
+    }
+
+    let mut page_str = String::new();
+    for category in ["ADM", "DIP", "MIL"] {
+        page_str += format!("== {category} ==\n").as_str();
+        page_str += "{| class=\"wikitable sortable\"\n|-\n! Policy !! Idea group !! Idea group !! Effects\n";
+        for policy in policies.iter().filter(|p| p.category == category) {
+            let groups = policy.idea_groups.iter()
+                .map(|g| format!("[[{}]]", deunicode(localise(g).as_str())))
+                .collect::<Vec<_>>();
+            page_str += format!(
+                "|-\n| {} || {} || {} || {}\n",
+                localise(&policy.idea.name),
+                groups.first().cloned().unwrap_or_default(),
+                groups.get(1).cloned().unwrap_or_default(),
+                idea_entries(&policy.idea, &localisations, &policy.idea.name, &mut warnings)
+            ).as_str();
+        }
+        page_str += "|}\n\n";
+    }
+    client.add_edit_page(&"Policies".to_string(), page_str);
+
+    if !warnings.is_empty() {
+        println!("Unknown modifiers in idea groups and policies:");
+        for warning in warnings {
+            println!("  {}", warning);
+        }
+    }
 }
 
 fn upload_flags(client: &mut MediaWikiClient) {
@@ -303,10 +489,74 @@ fn upload_flags(client: &mut MediaWikiClient) {
     }
 }
 
+// Everything the country pages need that isn't part of Country itself
+struct CountryPageContext {
+    mission_tags: HashSet<String>,
+    ideas: CountryIdeaSets,
+    localisations: HashMap<String, String>,
+    // TAG -> page title
+    names: HashMap<String, String>,
+    province_titles: BTreeMap<u64, String>,
+    // TAG -> (province id, development) owned at the start
+    owned: HashMap<String, Vec<(u64, u64)>>,
+    formables: Vec<Formable>,
+    snapshot: Snapshot,
+    governments: HashMap<String, Government>,
+    reforms: HashMap<String, GovernmentReform>,
+    reform_titles: HashMap<String, String>,
+}
+
+// Tier -> reforms of the country's government at the start, see reform_availability
+fn country_reforms(country: &Country, context: &CountryPageContext) -> BTreeMap<u8, Vec<(String, Availability)>> {
+    let state = country.timeline.state_at(start_date());
+    let Some(government) = context.governments.get(&state.government) else { return BTreeMap::new() };
+    reform_availability(government, &context.reforms, &context.snapshot.leaf(&country.tag))
+}
+
 fn country_list_and_details(client: &mut MediaWikiClient) {
     let mut countries = countries::parse_countries();
-    let mission_tags = tags_with_missions();
-    let formable_tags = formable_tags();
+    let super_regions = parse_map();
+    let localisations = parse_all_localisations();
+    let snapshot = Snapshot::at_start();
+    let mut owned: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+    for province in super_regions.iter()
+        .flat_map(|sr| sr.regions.iter())
+        .flat_map(|r| r.areas.iter())
+        .flat_map(|a| a.provinces.iter()) {
+        let state = province.start_state();
+        if let Some(owner) = state.owner.as_ref() {
+            owned.entry(owner.clone()).or_default().push((province.id, state.development()));
+        }
+    }
+    for provinces in owned.values_mut() {
+        provinces.sort();
+    }
+    let reforms = parse_government_reforms(Some(&localisations))
+        .into_iter()
+        .map(|r| (r.id.clone(), r))
+        .collect::<HashMap<_, _>>();
+    let context = CountryPageContext {
+        mission_tags: tags_with_missions(&snapshot),
+        ideas: parse_ideas_for(&snapshot),
+        localisations: localisations.clone(),
+        names: countries.iter().map(|c| (c.tag.clone(), deunicode(c.name.as_str()))).collect(),
+        province_titles: province_titles(&super_regions),
+        owned,
+        formables: parse_formables(),
+        snapshot,
+        governments: parse_governments().into_iter().map(|g| (g.id.clone(), g)).collect(),
+        reform_titles: reform_titles(reforms.values()),
+        reforms,
+    };
+    let mut seen_reforms = HashSet::new();
+    // the same decisions the country pages list, so a tag is formable here when it shows up
+    // as formable or possibly formable on some other country's page
+    let formable_tags = context.formables.iter()
+        .filter(|f| countries.iter().any(|c| {
+            c.tag != f.target && context.snapshot.evaluate(&c.tag, &f.potential) != Some(false)
+        }))
+        .map(|f| f.target.clone())
+        .collect::<HashSet<_>>();
 
     countries.sort_by(|a, b| a.tag.cmp(&b.tag));
 
@@ -328,133 +578,616 @@ fn country_list_and_details(client: &mut MediaWikiClient) {
             normal_name=normal_name,
             primary_culture=deunicode(country.history.primary_culture.as_str()),
             religion=deunicode(country.history.religion.as_str()),
-            missions=(||{if mission_tags.contains(&country.tag){"✅"} else {"❌"}})(),
+            missions=(||{if context.mission_tags.contains(&country.tag){"✅"} else {"❌"}})(),
             formable=(||{if formable_tags.contains(&country.tag){"✅"} else {"❌"}})(),
             egt=(||{if country.end_game_tag{"✅"} else {"❌"}})()
         ).as_str();
-        country_detail_page(client, country, &mission_tags);
+        let reforms = country_reforms(&country, &context);
+        seen_reforms.extend(reforms.values()
+            .flat_map(|tier| tier.iter())
+            .filter(|(_, availability)| *availability != Availability::Hidden)
+            .map(|(id, _)| id.clone()));
+        country_detail_page(client, country, &context, &reforms);
     }
     page_str += "|}\n";
     client.add_edit_page(&"Countries".to_string(), page_str);
+
+    let mut unseen = context.reforms.keys().filter(|id| !seen_reforms.contains(*id)).collect::<Vec<_>>();
+    if !unseen.is_empty() {
+        unseen.sort();
+        println!("Government reforms no country can see at the start:");
+        for id in unseen {
+            println!("  {}", id);
+        }
+    }
 }
 
-fn country_detail_page(client: &mut MediaWikiClient, country: Country, mission_tags: &HashSet<String>) {
-    let ideas = parse_ideas();
-    let idea_localisations = parse_idea_localisations();
+fn country_links(tags: &[String], names: &HashMap<String, String>) -> String {
+    tags.iter()
+        .map(|tag| names.get(tag).map(|n| format!("[[{}]]", n)).unwrap_or(tag.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn localised_list(keys: &[String], localisations: &HashMap<String, String>) -> String {
+    keys.iter()
+        .map(|key| localised_link(localisations, &Some(key.clone())))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
+fn country_detail_page(client: &mut MediaWikiClient, country: Country, context: &CountryPageContext, reforms: &BTreeMap<u8, Vec<(String, Availability)>>) {
+    let localisations = &context.localisations;
     let name = deunicode(country.name.as_str());
+    // country.history has localised culture and religion, the triggers need the ids
+    let state = country.timeline.state_at(start_date());
     let mut page_str = String::new();
     let mut set_name = "";
-    if let Some(set) = ideas.idea_sets.get(&country.tag) {
-        if let Some(name) = idea_localisations.get(&set.name) {
+    if let Some(set) = context.ideas.for_tag(&country.tag) {
+        if let Some(name) = localisations.get(&set.name) {
             set_name = name;
         }
     }
+    let rank = match state.government_rank {
+        1 => "Duchy",
+        2 => "Kingdom",
+        3 => "Empire",
+        _ => "",
+    };
+    let capital = match context.province_titles.get(&(state.capital as u64)) {
+        Some(title) => format!(
+            "[[{}|{}]]",
+            title,
+            localisations.get(&format!("PROV{}", state.capital)).cloned().unwrap_or_default()
+        ),
+        None => String::new(),
+    };
+    let owned = context.owned.get(&country.tag).cloned().unwrap_or_default();
+    let colour = country.data.colour
+        .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+        .unwrap_or_default();
     page_str += format!(
-        "{{{{Country Detail\n|tag={tag}\n|name={name}\n|primary_culture={culture}\n|religion={religion}\n|idea_group={ideas}\n}}}}\n",
+        "{{{{Country Detail\n|tag={tag}\n|name={name}\n|flag={tag} Flag.png\n|colour={colour}\n|primary_culture={culture}\n|religion={religion}\n|idea_group={ideas}\n",
         tag=country.tag,
         name=name,
+        colour=colour,
         culture=deunicode(country.history.primary_culture.as_str()),
         religion=deunicode(country.history.religion.as_str()),
         ideas=deunicode(set_name)
     ).as_str();
-    if mission_tags.contains(&country.tag) {
+    page_str += format!(
+        "|government={}\n|reforms={}\n|rank={}\n|technology_group={}\n|capital={}\n",
+        localised_link(localisations, &Some(state.government.clone())),
+        localised_list(&state.government_reforms, localisations),
+        rank,
+        localised_link(localisations, &Some(state.technology_group.clone())),
+        capital
+    ).as_str();
+    page_str += format!(
+        "|accepted_cultures={}\n|rivals={}\n|friends={}\n|provinces={}\n|development={}\n",
+        localised_list(&state.accepted_cultures, localisations),
+        country_links(&state.historical_rivals, &context.names),
+        country_links(&state.historical_friends, &context.names),
+        owned.len(),
+        owned.iter().map(|(_, dev)| dev).sum::<u64>()
+    ).as_str();
+    if context.mission_tags.contains(&country.tag) {
+        page_str += format!("|missions={} missions\n", name).as_str();
+    }
+    if let Some(graphical_culture) = &country.data.graphical_culture {
+        page_str += format!("|graphical_culture={}\n", graphical_culture).as_str();
+    }
+    page_str += format!(
+        "|historical_idea_groups={}\n",
+        localised_list(&country.data.historical_idea_groups, localisations)
+    ).as_str();
+    page_str += "}}\n";
+
+    page_str += ruler_sections(&country.timeline, localisations).as_str();
+
+    if !set_name.is_empty() {
+        // the idea pages are titled with the deunicoded set name
+        page_str += format!("\n== National ideas ==\n{{{{:{}}}}}\n", deunicode(set_name)).as_str();
+    }
+
+    // Some(true) means the history files alone say the decision shows up, None that it depends
+    // on something they can't answer
+    let leaf = context.snapshot.leaf(&country.tag);
+    let formable_links = |result: Option<bool>| {
+        let mut targets = context.formables.iter()
+            .filter(|f| f.target != country.tag && f.potential.evaluate(&leaf) == result)
+            .filter_map(|f| context.names.get(&f.target))
+            .map(|n| format!("[[{}]]", n))
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets.dedup();
+        targets
+    };
+    let formable = formable_links(Some(true));
+    let maybe_formable = formable_links(None)
+        .into_iter()
+        .filter(|t| !formable.contains(t))
+        .collect::<Vec<_>>();
+    if !formable.is_empty() || !maybe_formable.is_empty() {
+        page_str += "\n== Formable nations ==\n";
+        if !formable.is_empty() {
+            page_str += format!("* {}\n", formable.join("\n* ")).as_str();
+        }
+        if !maybe_formable.is_empty() {
+            page_str += format!("May be able to form, depending on the situation:\n* {}\n", maybe_formable.join("\n* ")).as_str();
+        }
+    }
+
+    page_str += reform_section(&name, &state.government, reforms, context).as_str();
+    page_str += name_section(&country.data).as_str();
+
+    if !owned.is_empty() {
+        page_str += "\n== Provinces ==\n{| class=\"wikitable sortable\"\n|-\n! ID !! Province !! Development\n";
+        for (id, development) in owned.iter() {
+            let province = context.province_titles.get(id).cloned().unwrap_or(id.to_string());
+            page_str += format!("|-\n| {} || [[{}]] || {}\n", id, province, development).as_str();
+        }
+        page_str += "|}\n";
+    }
+
+    if context.mission_tags.contains(&country.tag) {
         page_str += "\n[[Category:Countries with missions]]\n";
     }
     client.add_edit_page(&name, page_str);
 }
 
-fn racial_modifiers(client: &mut MediaWikiClient) {
-    let data = fs::read("./anbennar/common/event_modifiers/racial_admin_military.txt")
-        .expect("Could not find racial modifiers file");
-    let tape = TextTape::from_slice(data.as_slice()).unwrap();
-    let reader = tape.windows1252_reader();
+fn reform_section(name: &str, government: &str, reforms: &BTreeMap<u8, Vec<(String, Availability)>>, context: &CountryPageContext) -> String {
+    let Some(government) = context.governments.get(government) else { return String::new() };
+    let link = |id: &String| match context.reforms.get(id) {
+        Some(reform) => format!("[[{}|{}]]", context.reform_titles[id], reform.name.as_deref().unwrap_or(id.as_str())),
+        None => id.to_string(),
+    };
+    let mut page_str = format!(
+        "\n== Reforms available to {} ==\n{{| class=\"wikitable\"\n|-\n! Tier !! Selectable !! Potentially available\n",
+        name
+    );
+    for (tier, available) in reforms.iter() {
+        let level = government.reform_levels.get(tier)
+            .map(|level| localised_name(&context.localisations, &level.id))
+            .unwrap_or_default();
+        let with = |wanted: Availability| available.iter()
+            .filter(|(_, availability)| *availability == wanted)
+            .map(|(id, _)| link(id))
+            .collect::<Vec<_>>()
+            .join("<br>");
+        page_str += format!(
+            "|-\n| {}: {} || {} || {}\n",
+            tier, level, with(Availability::Selectable), with(Availability::Potential)
+        ).as_str();
+    }
+    page_str += "|}\n";
+    page_str
+}
+
+fn name_section(data: &CountryData) -> String {
     let mut page_str = String::new();
+    let mut name_list = |title: &str, names: Vec<String>| {
+        if !names.is_empty() {
+            page_str += format!("\n=== {} ===\n{}\n", title, names.join(", ")).as_str();
+        }
+    };
+    let monarch_names = |female: bool| data.monarch_names.iter()
+        .filter(|(_, chance)| (*chance < 0) == female)
+        .map(|(name, _)| deunicode(name.as_str()))
+        .collect::<Vec<_>>();
+    name_list("Monarch names", monarch_names(false));
+    name_list("Female monarch names", monarch_names(true));
+    name_list("Leader names", data.leader_names.iter().map(|n| deunicode(n)).collect());
+    name_list("Army names", data.army_names.iter().map(|n| deunicode(n)).collect());
+    name_list("Ship names", data.ship_names.iter().map(|n| deunicode(n)).collect());
+    name_list("Fleet names", data.fleet_names.iter().map(|n| deunicode(n)).collect());
+    if page_str.is_empty() {
+        return page_str;
+    }
+    format!("\n== Names ==\n{}", page_str)
+}
 
-    for (key, _op, value) in reader.fields() {
-        if key.read_str().ends_with("administration") || key.read_str().ends_with("military") {
-            let key = key.read_string();
-            let title: Vec<_> = key.split('_').collect();
-            let title = title.iter().map(|t| title_case(t)).collect::<Vec<_>>().join(" ");
-            if let Ok(modifiers) = value.read_object() {
-                page_str += format!("=== {} ===\n", title).as_str();
-                for (key, _op, value) in modifiers.fields() {
-                    if key.read_str() != "picture" {
-                        if let Some(modifier) = get_modifier(&key.read_string(), ) {
-                            if let Ok(value) = value.read_scalar() {
-                                let mut colour = "bonus";
-                                let scalar = value.to_f64();
-                                if let Ok(value) = scalar {
-                                    if value.is_sign_positive() && modifier.normal == Negative || value.is_sign_negative() && modifier.normal == Positive {
-                                        colour = "malus";
-                                    }
-                                    let value = modifier.to_human_readable(value as f32);
-                                    page_str += format!("* {{{{subst:Modifier |type={}|value={}|description={} }}}}\n", colour, value, modifier.name).as_str()
-                                } else {
-                                    page_str += format!("* {{{{subst:Modifier |type={}|value={}|description={} }}}}\n", colour, value, modifier.name).as_str()
-                                }
-                            }
-                        }
-                    }
-                }
-                page_str += "\n";
+fn ruler_row(ruler: &Ruler, localisations: &HashMap<String, String>) -> String {
+    let culture = ruler.culture.as_ref().map(|c| localisations.get(c).unwrap_or(c).to_string()).unwrap_or_default();
+    format!(
+        "|-\n| {} || {} || {} || {} || {} / {} / {} || {} || {}\n",
+        ruler.date.game_fmt(),
+        ruler.kind.as_str(),
+        deunicode(ruler.name.as_str()),
+        deunicode(ruler.dynasty.as_deref().unwrap_or("")),
+        ruler.adm, ruler.dip, ruler.mil,
+        title_case(ruler.race().unwrap_or("")),
+        deunicode(culture.as_str())
+    )
+}
+
+fn ruler_sections(timeline: &CountryTimeline, localisations: &HashMap<String, String>) -> String {
+    let header = "{| class=\"wikitable\"\n|-\n! Date !! Title !! Name !! Dynasty !! Stats !! Race !! Culture\n";
+    let mut page_str = String::new();
+    let starting = [RulerKind::Monarch, RulerKind::Heir, RulerKind::Queen]
+        .iter()
+        .filter_map(|kind| timeline.ruler_at(*kind, start_date()))
+        .collect::<Vec<_>>();
+    if !starting.is_empty() {
+        page_str += "\n== Starting ruler ==\n";
+        page_str += header;
+        for ruler in starting {
+            page_str += ruler_row(ruler, localisations).as_str();
+        }
+        page_str += "|}\n";
+    }
+    let monarchs = timeline.monarchs();
+    if monarchs.len() > 1 {
+        page_str += "\n== Historical rulers ==\n";
+        page_str += header;
+        for ruler in monarchs {
+            page_str += ruler_row(ruler, localisations).as_str();
+        }
+        page_str += "|}\n";
+    }
+    page_str
+}
+
+fn race_title(race: &str) -> String {
+    let name = race.split('_').map(title_case).collect::<Vec<_>>().join(" ");
+    format!("{} (race)", name)
+}
+
+fn run_races(client: &mut MediaWikiClient) {
+    let localisations = parse_all_localisations();
+    let mut groups = parse_cultures();
+    let unassigned = assign_races(&mut groups, &parse_country_timelines());
+    let races = parse_races(&groups);
+
+    let mut index_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Race !! Modifiers !! Culture groups\n");
+    for race in races.iter() {
+        let title = race_title(&race.id);
+        let mut page_str = String::new();
+        let mut kind = None;
+        for modifier in race.modifiers.iter() {
+            if kind != Some(modifier.kind) {
+                page_str += format!("== {} ==\n", modifier.kind.as_str()).as_str();
+                kind = Some(modifier.kind);
             }
+            let name = localisations.get(&modifier.id)
+                .filter(|n| !n.is_empty())
+                .cloned()
+                .unwrap_or(modifier.variant.split('_').map(title_case).collect::<Vec<_>>().join(" "));
+            if !name.is_empty() {
+                page_str += format!("=== {} ===\n", deunicode(name.as_str())).as_str();
+            }
+            add_modifiers(&mut page_str, &modifier.modifiers);
+            page_str += "\n";
+        }
+
+        let culture_groups = groups.iter()
+            .filter(|g| race.culture_groups.contains(&g.id))
+            .map(|g| format!("[[{}]]", culture_group_title(g, &localisations)))
+            .collect::<Vec<_>>();
+        if !culture_groups.is_empty() {
+            page_str += format!("== Culture groups ==\n* {}\n", culture_groups.join("\n* ")).as_str();
+        }
+        page_str += "\n<noinclude>[[Category:Races]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+
+        index_page += format!(
+            "|-\n| [[{}]] || {} || {}\n",
+            title,
+            race.modifiers.len(),
+            culture_groups.join(", ")
+        ).as_str();
+    }
+    index_page += "|}\n";
+    if !unassigned.is_empty() {
+        index_page += "\n== Culture groups without a race ==\n";
+        let group_link = |id: &String| groups.iter()
+            .find(|g| &g.id == id)
+            .map(|g| format!("[[{}]]", culture_group_title(g, &localisations)))
+            .unwrap_or(id.clone());
+        for group in unassigned {
+            index_page += match group {
+                UnassignedRace::NoVotes(id) => format!("* {}: no starting monarch has a racial personality\n", group_link(&id)),
+                UnassignedRace::Tied(id, tied) => format!(
+                    "* {}: tied between {}\n",
+                    group_link(&id),
+                    tied.iter().map(|personality| match races.iter().find(|r| race_stem(&r.id) == race_stem(personality)) {
+                        Some(race) => format!("[[{}]]", race_title(&race.id)),
+                        None => personality.split('_').map(title_case).collect::<Vec<_>>().join(" "),
+                    }).collect::<Vec<_>>().join(" and ")
+                ),
+            }.as_str();
         }
     }
+    client.add_edit_page(&"Races".to_string(), index_page);
+    // --racial-modifiers used to write everything to this page
+    client.redirect(&"Racial_Modifiers".to_string(), &"Races".to_string());
+}
 
-    client.add_edit_page(&String::from("Racial_Modifiers"), page_str);
+fn religious_group_title(group: &ReligiousGroup, localisations: &HashMap<String, String>) -> String {
+    let name = localisations.get(&group.id).filter(|n| !n.is_empty()).cloned().unwrap_or(title_case(&group.id));
+    format!("{} (religious group)", deunicode(name.as_str()))
+}
+
+fn localised_name(localisations: &HashMap<String, String>, id: &str) -> String {
+    deunicode(localisations.get(id).filter(|n| !n.is_empty()).cloned().unwrap_or(title_case(id)).as_str())
+}
+
+// The generators that redirect a plain name to a section or a suffixed page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Redirects {
+    Religions,
+    Cultures,
+    Reforms,
+}
+
+// Plain titles a redirect must leave alone: country and government pages, and the names the
+// other kinds of redirect claim, so a name two of them share gets no redirect at all
+fn taken_titles(localisations: &HashMap<String, String>, kind: Redirects) -> HashSet<String> {
+    let mut taken = countries::parse_country_tags()
+        .into_iter()
+        .filter_map(|(tag, _path)| localisations.get(&tag).filter(|n| !n.is_empty()).map(|n| deunicode(n)))
+        .collect::<HashSet<_>>();
+    taken.extend(parse_governments().iter().map(|g| localised_name(localisations, &g.id)));
+    if kind != Redirects::Religions {
+        taken.extend(parse_religious_groups(None).iter()
+            .flat_map(|g| g.religions.keys())
+            .map(|id| localised_name(localisations, id)));
+    }
+    if kind != Redirects::Cultures {
+        taken.extend(parse_cultures().iter()
+            .flat_map(|g| g.cultures.iter())
+            .map(|c| localised_name(localisations, &c.id)));
+    }
+    if kind != Redirects::Reforms {
+        taken.extend(parse_government_reforms(Some(localisations)).iter()
+            .filter_map(|r| r.name.as_deref())
+            .map(deunicode));
+    }
+    taken
+}
+
+fn redirect_unless_taken(client: &mut MediaWikiClient, taken: &mut HashSet<String>, source: &String, target: &String) {
+    if taken.insert(source.clone()) {
+        client.redirect(source, target);
+    } else {
+        println!("Not redirecting {} to {}, the title is already taken", source, target);
+    }
 }
 
 fn run_religions(client: &mut MediaWikiClient) {
     let localisations = parse_all_localisations();
     let religious_groups = parse_religious_groups(Some(&localisations));
-    println!("{:?}", religious_groups);
+    let church_aspects = parse_church_aspects();
+    let personal_deities = parse_personal_deities();
+    let fetishist_cults = parse_fetishist_cults();
+    let religious_reforms = parse_religious_reforms();
+    let mut taken = taken_titles(&localisations, Redirects::Religions);
+    let super_regions = parse_map();
+    let titles = province_titles(&super_regions);
+    let provinces = super_regions.iter()
+        .flat_map(|sr| sr.regions.iter())
+        .flat_map(|r| r.areas.iter())
+        .flat_map(|a| a.provinces.iter())
+        .map(|p| (p.id, (format!("[[{}|{}]]", titles.get(&p.id).cloned().unwrap_or_default(), deunicode(p.name.as_str())), p.start_state())))
+        .collect::<HashMap<_, _>>();
+    // the province with its owner at the start
+    let province_link = |id: &u64| match provinces.get(id) {
+        Some((link, state)) if state.owner.is_some() => format!("{} ({})", link, localised_link(&localisations, &state.owner)),
+        Some((link, _)) => link.clone(),
+        None => format!("Province {}", id),
+    };
+    let adherents = count_adherents(provinces.values().map(|(_, state)| state));
+    let total_development = adherents.values().map(|a| a.development).sum::<u64>().max(1);
+    let mut adherents_rows = vec![];
 
+    let mut index_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Religious group !! Religions\n");
     for religious_group in religious_groups {
-        match religious_group.id.as_str() {
-            name => {
-                let mut page_str = String::new();
-                for religion_raw in religious_group.religions.keys() {
-                    let religion = religious_group.religions.get(religion_raw).unwrap();
-                    let religion_name = localisations.get(&religion.id).unwrap();
-                    let religious_desc = localisations.get(&format!("{}_religion_desc", &religion.id).to_string()).cloned();
-                    // let religious_desc = localisations.get(&format!("{key}_desc").to_string()).cloned();
-                    page_str += format!("=== {religion} ===\n", religion=religion_name).as_str();
-                    if religious_desc.is_some() {
-                        page_str += format!("{{{{ReligiousDescription|religion={religion}|description={desc}}}}}\n", religion=&religion.id, desc=religious_desc.unwrap()).as_str();
+        let title = religious_group_title(&religious_group, &localisations);
+        let mut page_str = format!(
+            "{{{{Religious Group\n|name={}\n|crusade_name={}\n}}}}\n",
+            title.trim_end_matches(" (religious group)"),
+            religious_group.crusade_name.as_deref().map(|c| localised_name(&localisations, c)).unwrap_or_default()
+        );
+        if religious_group.center_of_religion != 0 {
+            page_str += format!("\n'''Center of reformation:''' {}\n", province_link(&religious_group.center_of_religion)).as_str();
+        }
+        if let Some(harmonized) = &religious_group.harmonized_modifier {
+            page_str += format!("\nHarmonizing this group gives [[{}]].\n", localised_name(&localisations, harmonized)).as_str();
+        }
+        if let Some(schools) = religious_group.schools.as_ref().filter(|s| !s.is_empty()) {
+            page_str += "\n== Schools ==\n";
+            for school in schools.values() {
+                page_str += format!("\n=== {} ===\n", localised_name(&localisations, &school.id)).as_str();
+                if let Some(picture) = &school.picture {
+                    page_str += format!("[[File:{}.png|{}]]\n\n", picture, localised_name(&localisations, &school.id)).as_str();
+                }
+                add_modifiers(&mut page_str, &school.country_modifiers);
+            }
+        }
+
+        for religion in religious_group.religions.values() {
+            let religion_name = localised_name(&localisations, &religion.id);
+            page_str += format!("\n== {} ==\n", religion_name).as_str();
+            if let Some(desc) = localisations.get(&format!("{}_religion_desc", &religion.id)) {
+                page_str += format!("{{{{ReligiousDescription|religion={}|description={}}}}}\n", &religion.id, desc).as_str();
+            }
+            if let Some(adherents) = adherents.get(&religion.id) {
+                page_str += format!(
+                    "'''Provinces at the start:''' {} with {} development\n\n",
+                    adherents.provinces, adherents.development
+                ).as_str();
+                adherents_rows.push((adherents.clone(), religion_name.clone(), title.clone()));
+            }
+            if !religion.country_modifiers.is_empty() {
+                page_str += format!("All {} countries receive:\n", religion_name).as_str();
+                add_modifiers(&mut page_str, &religion.country_modifiers);
+            }
+            if !religion.country_as_secondary_modifiers.is_empty() {
+                page_str += format!("\nCountries with {} as secondary religion receive:\n", religion_name).as_str();
+                add_modifiers(&mut page_str, &religion.country_as_secondary_modifiers);
+            }
+            if !religion.province_modifiers.is_empty() {
+                page_str += format!("\nAll {} provinces receive:\n", religion_name).as_str();
+                add_modifiers(&mut page_str, &religion.province_modifiers);
+            }
+            if !religion.holy_sites.is_empty() {
+                page_str += format!(
+                    "\n'''Holy sites:''' {}\n",
+                    religion.holy_sites.iter().map(province_link).collect::<Vec<_>>().join(", ")
+                ).as_str();
+            }
+            for (heading, ids) in [("Aspects", &religion.aspects), ("Blessings", &religion.blessings)] {
+                if ids.is_empty() {
+                    continue;
+                }
+                page_str += format!("\n=== {} ===\n", heading).as_str();
+                for id in ids {
+                    page_str += format!("; {}\n", localised_name(&localisations, id)).as_str();
+                    if let Some(desc) = localisations.get(&format!("{}_desc", id)) {
+                        page_str += format!(": {}\n", desc).as_str();
                     }
-                    page_str += format!("All {} countries receive:\n", religion_name).as_str();
-                    add_modifiers(&mut page_str, &religion.country_modifiers);
-                    if ! &religion.province_modifiers.is_empty() {
-                        page_str += format!("All {} provinces receive:\n", religion_name).as_str();
-                        add_modifiers(&mut page_str, &religion.province_modifiers);
+                    if let Some(aspect) = church_aspects.get(id) {
+                        add_modifiers(&mut page_str, &aspect.modifiers);
                     }
-                    page_str += "\n";
+                }
+            }
+            if !religion.orthodox_icons.is_empty() {
+                page_str += "\n=== Icons ===\n";
+                for icon in religion.orthodox_icons.values() {
+                    page_str += format!("; {}\n", localised_name(&localisations, &icon.id)).as_str();
+                    add_modifiers(&mut page_str, &icon.country_modifiers);
+                }
+            }
+            if let Some(papacy) = &religion.papacy {
+                page_str += "\n=== Papacy ===\n";
+                if let Some(tag) = &papacy.papal_tag {
+                    page_str += format!("Papal state: {}\n\n", localised_link(&localisations, &Some(tag.clone()))).as_str();
+                }
+                if let Some(seat) = &papacy.seat_of_papacy {
+                    page_str += format!("Seat of the papacy: {}\n\n", province_link(seat)).as_str();
+                }
+                if !papacy.concessions.is_empty() {
+                    page_str += "{| class=\"wikitable\"\n|-\n! Concession !! Harsh !! Conciliatory\n";
+                    for (id, concession) in papacy.concessions.iter() {
+                        let mut harsh = String::new();
+                        let mut concilatory = String::new();
+                        add_modifiers(&mut harsh, &concession.harsh);
+                        add_modifiers(&mut concilatory, &concession.concilatory);
+                        page_str += format!("|-\n| {} ||\n{}||\n{}\n", localised_name(&localisations, id), harsh, concilatory).as_str();
+                    }
+                    page_str += "|}\n";
+                }
+            }
 
-                    //println!("{}, {}", &title_case(religion_name), format!("#REDIRECT [[{}#{}]]", &title_case(name), &title_case(religion_name).replace(" ", "_")));
-                    // client.add_edit_page(&title_case(religion_name), format!("#REDIRECT [[{}#{}]]", &title_case(name), &title_case(religion_name).replace(" ", "_")));
-                    // thread::sleep(time::Duration::from_secs(5)); // quotas
+            let mechanics = [
+                (religion.uses_karma, "Karma"),
+                (religion.uses_harmony, "Harmony"),
+                (religion.fervor, "Fervor"),
+                (religion.doom, "Doom"),
+                (religion.ancestors, "Ancestors"),
+                (religion.personal_deity, "Personal deities"),
+                (religion.fetishist_cult, "Fetishist cults"),
+                (religion.religious_reforms, "Religious reforms"),
+            ].iter().filter(|(used, _)| *used).map(|(_, name)| *name).collect::<Vec<_>>();
+            if !mechanics.is_empty() {
+                page_str += format!("\n'''Mechanics:''' {}\n", mechanics.join(", ")).as_str();
+            }
+            if !religion.heretic.is_empty() {
+                page_str += format!(
+                    "\n'''Heresies:''' {}\n",
+                    religion.heretic.iter().map(|h| localised_name(&localisations, h)).collect::<Vec<_>>().join(", ")
+                ).as_str();
+            }
+            if !religion.allowed_conversion.is_empty() {
+                page_str += format!(
+                    "\n'''Can convert to:''' {}\n",
+                    religion.allowed_conversion.iter().map(|r| format!("[[{}]]", localised_name(&localisations, r))).collect::<Vec<_>>().join(", ")
+                ).as_str();
+            }
+            if let Some(celebrate) = &religion.celebrate {
+                page_str += format!("\n'''Celebrates:''' {}\n", localised_name(&localisations, celebrate)).as_str();
+            }
+            let mut deity_sections = vec![];
+            if religion.personal_deity {
+                deity_sections.push(("Personal deities", personal_deities.iter().filter(|d| d.religions.contains(&religion.id)).collect::<Vec<_>>()));
+            }
+            if religion.fetishist_cult {
+                deity_sections.push(("Fetishist cults", fetishist_cults.iter().filter(|c| c.religions.is_empty() || c.religions.contains(&religion.id)).collect::<Vec<_>>()));
+            }
+            for (heading, deities) in deity_sections {
+                if deities.is_empty() {
+                    continue;
+                }
+                page_str += format!("\n=== {} ===\n", heading).as_str();
+                for deity in deities {
+                    page_str += format!("; {}\n", localised_name(&localisations, &deity.id)).as_str();
+                    add_modifiers(&mut page_str, &deity.modifiers);
+                }
+            }
+            if !religion.gurus.is_empty() {
+                page_str += "\n=== Gurus ===\n";
+                for guru in religion.gurus.iter() {
+                    page_str += format!("* {}\n", localised_name(&localisations, guru)).as_str();
                 }
-                //println!("!!!{}", name);
-                //println!("{}", page_str);
-                // client.add_edit_page(&title_case(name), page_str);
             }
+            let reform_sets = religious_reforms.iter()
+                .filter(|r| r.religions.contains(&religion.id))
+                .collect::<Vec<_>>();
+            if !reform_sets.is_empty() {
+                page_str += "\n=== Religious reforms ===\n";
+            }
+            for reform_set in reform_sets {
+                for reform in reform_set.reforms.iter() {
+                    page_str += format!("; {}\n", localised_name(&localisations, &reform.id)).as_str();
+                    add_modifiers(&mut page_str, &reform.modifiers);
+                }
+            }
+
+            redirect_unless_taken(client, &mut taken, &religion_name, &format!("{}#{}", title, religion_name));
         }
+        page_str += "\n<noinclude>[[Category:Religious groups]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+
+        index_page += format!(
+            "|-\n| [[{}]] || {}\n",
+            title,
+            religious_group.religions.values()
+                .map(|r| format!("[[{}]]", localised_name(&localisations, &r.id)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ).as_str();
+    }
+    index_page += "|}\n";
+    client.add_edit_page(&"Religions".to_string(), index_page);
+
+    adherents_rows.sort_by(|a, b| b.0.development.cmp(&a.0.development).then(a.1.cmp(&b.1)));
+    let mut adherents_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Religion !! Religious group !! Provinces !! Development !! Share of development\n");
+    for (adherents, religion_name, group_title) in adherents_rows {
+        adherents_page += format!(
+            "|-\n| [[{}]] || [[{}|{}]] || {} || {} || {:.1}%\n",
+            religion_name,
+            group_title,
+            group_title.trim_end_matches(" (religious group)"),
+            adherents.provinces,
+            adherents.development,
+            adherents.development as f64 * 100.0 / total_development as f64
+        ).as_str();
     }
+    adherents_page += "|}\n";
+    client.add_edit_page(&"Religions by adherents".to_string(), adherents_page);
+}
+
+// One list item per modifier, `bullet` is * or ** for a nested list
+fn modifier_lines(modifiers: &BTreeMap<String, Vec<u8>>, bullet: &str) -> Vec<String> {
+    format_modifiers(modifiers)
+        .iter()
+        .map(|formatted| format!("{} {}", bullet, formatted.to_template(false)))
+        .collect()
 }
 
 fn add_modifiers(page_str: &mut String, modifiers: &BTreeMap<String, Vec<u8>>) {
-    for (modifier_key, modifier_value) in modifiers {
-        let modifier = get_modifier(&modifier_key, ).unwrap();
-        let value = Scalar::new(modifier_value.as_slice());
-        if let Ok(value) = value.to_f64() {
-            let mut colour = "bonus";
-            if value.is_sign_positive() && modifier.normal == Negative || value.is_sign_negative() && modifier.normal == Positive {
-                colour = "malus";
-            }
-            let value = modifier.to_human_readable(value as f32);
-            *page_str += format!("* {{{{Modifier |type={}|value={}|description={} }}}}\n", colour, value, modifier.name).as_str();
-        }
+    for line in modifier_lines(modifiers, "*") {
+        *page_str += format!("{}\n", line).as_str();
     }
 }
 
@@ -480,6 +1213,98 @@ fn run_government_icons(client: &mut MediaWikiClient) {
     }
 }
 
+// Reform names aren't unique, the duplicates get their id appended
+fn reform_titles<'a>(reforms: impl Iterator<Item = &'a GovernmentReform>) -> HashMap<String, String> {
+    let reforms = reforms.collect::<Vec<_>>();
+    let name = |reform: &GovernmentReform| deunicode(reform.name.as_deref().unwrap_or(reform.id.as_str()));
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for reform in reforms.iter() {
+        *counts.entry(name(reform)).or_insert(0) += 1;
+    }
+    reforms.iter()
+        .map(|r| {
+            let title = if counts.get(&name(r)).is_some_and(|c| *c > 1) {
+                format!("{} ({})", name(r), r.id)
+            } else {
+                format!("{} (government reform)", name(r))
+            };
+            (r.id.clone(), title)
+        })
+        .collect()
+}
+
+fn reform_icon(reform: &GovernmentReform) -> String {
+    match reform.icon.as_ref() {
+        None => "[[File:gov_placeholder.png]]".to_string(),
+        Some(icon) => format!("[[File:gov_{}.png]]", icon),
+    }
+}
+
+fn reform_potential(reform: &GovernmentReform) -> String {
+    reform.potential.as_ref().map(|potential| htmlify(&potential.to_json())).unwrap_or_default()
+}
+
+fn reform_trigger(reform: &GovernmentReform) -> String {
+    reform.trigger.as_ref().map(|trigger| htmlify(&trigger.to_json())).unwrap_or_default()
+}
+
+// Switches the reform turns on, e.g. {{Reform attribute|has parliament}}
+fn reform_badges(reform: &GovernmentReform) -> String {
+    let switches = [
+        ("monarchy", reform.monarchy),
+        ("republic", reform.republic),
+        ("lock_level_when_selected", reform.lock_level_when_selected),
+    ];
+    switches.iter()
+        .filter(|(_, switch)| *switch == Some(true))
+        .map(|(id, _)| *id)
+        .chain(reform.attributes.iter().chain(reform.custom_attributes.iter()).filter(|(_, switch)| **switch).map(|(id, _)| id.as_str()))
+        .map(|id| format!("{{{{Reform attribute|{}}}}}", id.replace('_', " ")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// "If X then Y" for every conditional block, as a nested list
+fn reform_conditionals(reform: &GovernmentReform) -> String {
+    let mut text = String::new();
+    for conditional in reform.conditional.iter() {
+        text += format!("\n* If {}", htmlify(&conditional.allow.to_json())).as_str();
+        for line in modifier_lines(&conditional.modifiers, "**") {
+            text += format!("\n{}", line).as_str();
+        }
+        for (id, _) in conditional.attributes.iter().filter(|(_, switch)| **switch) {
+            text += format!("\n** {{{{Reform attribute|{}}}}}", id.replace('_', " ")).as_str();
+        }
+    }
+    text
+}
+
+fn reform_row(reform: &GovernmentReform, title: &str) -> String {
+    let mut requirements = reform_potential(reform);
+    let trigger = reform_trigger(reform);
+    if !trigger.is_empty() {
+        requirements += format!("<br>Can be enacted if:<br>{}", trigger).as_str();
+    }
+    let mut row = format!(
+        "|-\n|| {} || [[{}|{}]] {}\n|| {}|| ",
+        reform_icon(reform),
+        title,
+        reform.name.as_deref().unwrap_or(reform.id.as_str()),
+        reform_badges(reform),
+        requirements
+    );
+    for line in modifier_lines(&reform.modifiers, "*") {
+        row += format!("\n{}", line).as_str()
+    }
+    row += reform_conditionals(reform).as_str();
+    row += "\n|";
+    match reform.desc.as_ref() {
+        None => row += "|\n",
+        Some(desc) => row += format!("| {}\n", desc.replace("\\n", "<br>")).as_str(),
+    }
+    row
+}
+
 fn run_governments(client: &mut MediaWikiClient) {
     let governments = parse_governments();
     let localisations = parse_all_localisations();
@@ -488,73 +1313,106 @@ fn run_governments(client: &mut MediaWikiClient) {
         .iter()
         .map(|v|(v.id.clone(), v))
         .collect::<HashMap<_, _>>();
+    let mut warnings = vec![];
+    // reform -> the government tiers offering it
+    let mut used_in: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let titles = reform_titles(reforms.values().copied());
+    let mut taken = taken_titles(&localisations, Redirects::Reforms);
 
-    for government in governments {
-        match government.id.as_str() {
-            "pre_dharma_mapping" => {}
-            name => {
-                let mut page_str = String::new();
-                for level in government.reform_levels.keys() {
-                    let reform_level = government.reform_levels.get(level).unwrap();
-                    let level_name = localisations.get(&reform_level.id).unwrap();
-                    page_str += format!("=== Tier {level}: {name} ===\n", level=level, name=level_name).as_str();
-                    page_str += "{| class=\"reformtable\" \n|-\n! Icon !! Name !! Potential !! Effects !! Description\n";
-                    for reform_id in &reform_level.reforms {
-                        let reform = reforms.get(reform_id).unwrap();
-                        if reform.name.as_ref().is_none() {
-                            continue;
-                        }
-                        page_str += "|-\n";
-                        match reform.icon.as_ref() {
-                            None => {
-                                page_str += "|| [[File:gov_placeholder.png]] "
-                            }
-                            Some(icon) => {
-                                page_str += format!("|| [[File:gov_{}.png]] ", icon).as_str()
-                            }
-                        }
-                        page_str += format!("|| {} \n", reform.name.as_ref().unwrap()).as_str();
-                        if reform.potential.is_some() {
-                            page_str += "|| ";
-                            page_str += htmlify(&serde_json::from_str::<Value>(<Option<String> as Clone>::clone(&reform.potential).expect("could not unwrap").as_str()).unwrap()).as_str();
-                        } else {
-                            page_str += "|| ";
-                        }
-                        page_str += "|| ";
-                        for (modifier_key, modifier_value) in &reform.modifiers {
-                            let modifier = get_modifier(&modifier_key, ).unwrap();
-                            let value = Scalar::new(modifier_value.as_slice());
-                            if let Ok(value) = value.to_f64() {
-                                let mut colour = "bonus";
-                                if value.is_sign_positive() && modifier.normal == Negative || value.is_sign_negative() && modifier.normal == Positive {
-                                    colour = "malus";
-                                }
-                                let value = modifier.to_human_readable(value as f32);
-                                page_str += format!("\n* {{{{subst:Modifier |type={}|value={}|description={} }}}}", colour, value, modifier.name).as_str()
-                            }
-                        }
-                        page_str += "\n|";
-
-                        match reform.desc.as_ref() {
-                            None => {page_str += "|\n"}
-                            Some(mut desc) => {
-                                let desc = desc.replace("\\n", "<br>");
-                                page_str += format!("| {}\n", desc).as_str();
-                            }
-                        }
-                    }
-                    page_str += "|}\n";
+    for government in governments.iter() {
+        if government.id == "pre_dharma_mapping" {
+            continue;
+        }
+        let title = localised_name(&localisations, &government.id);
+        let mut page_str = String::new();
+        for (level, reform_level) in government.reform_levels.iter() {
+            let level_name = match localisations.get(&reform_level.id) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => {
+                    warnings.push(format!("{}: no localisation for reform level {}", government.id, reform_level.id));
+                    reform_level.id.clone()
+                }
+            };
+            page_str += format!("=== Tier {level}: {name} ===\n", level=level, name=level_name).as_str();
+            page_str += "{| class=\"reformtable\" \n|-\n! Icon !! Name !! Potential !! Effects !! Description\n";
+            for reform_id in &reform_level.reforms {
+                let Some(reform) = reforms.get(reform_id) else {
+                    warnings.push(format!("{}: unknown reform {}", government.id, reform_id));
+                    continue;
+                };
+                if reform.name.is_none() {
+                    warnings.push(format!("{}: no localisation for reform {}", government.id, reform_id));
                 }
-                println!("{}", page_str);
-                // client.add_edit_page(&title_case(name), page_str);
+                page_str += reform_row(reform, &titles[reform_id]).as_str();
+                used_in.entry(reform_id.clone()).or_default().push(format!("[[{}]] tier {}: {}", title, level, level_name));
+            }
+            page_str += "|}\n";
+        }
+        page_str += "\n<noinclude>[[Category:Governments]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+    }
+
+    for (reform_id, governments) in used_in.iter() {
+        let reform = reforms.get(reform_id).unwrap();
+        let title = titles[reform_id].clone();
+        let mut page_str = format!(
+            "{{{{Government Reform\n|name={}\n|icon={}\n|description={}\n}}}}\n",
+            reform.name.as_deref().unwrap_or(reform.id.as_str()),
+            reform_icon(reform),
+            reform.desc.as_deref().unwrap_or("").replace("\\n", "<br>")
+        );
+        let badges = reform_badges(reform);
+        if !badges.is_empty() {
+            page_str += format!("\n{}\n", badges).as_str();
+        }
+        if let Some(cost) = reform.nation_designer_cost {
+            page_str += format!("\nNation designer cost: {}\n", cost).as_str();
+        }
+        if !reform.modifiers.is_empty() || !reform.conditional.is_empty() {
+            page_str += "\n== Effects ==\n";
+            add_modifiers(&mut page_str, &reform.modifiers);
+            page_str += reform_conditionals(reform).trim_start();
+            page_str += "\n";
+        }
+        for (heading, effect) in [("When enacted", &reform.effect), ("When removed", &reform.removed_effect)] {
+            if let Some(value) = effect.as_ref().and_then(|e| serde_json::from_str::<Value>(e.as_str()).ok()) {
+                page_str += format!("\n=== {} ===\n{}\n", heading, htmlify(&value)).as_str();
             }
         }
+        let potential = reform_potential(reform);
+        if !potential.is_empty() {
+            page_str += format!("\n== Potential ==\n{}\n", potential).as_str();
+        }
+        let trigger = reform_trigger(reform);
+        if !trigger.is_empty() {
+            page_str += format!("\n== Requirements ==\n{}\n", trigger).as_str();
+        }
+        page_str += "\n== Governments ==\n";
+        for government in governments {
+            page_str += format!("* {}\n", government).as_str();
+        }
+        page_str += "\n<noinclude>[[Category:Government reforms]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+
+        // a reform sharing its name with a government keeps the government page
+        if let Some(name) = reform.name.as_ref().map(|n| deunicode(n)) {
+            redirect_unless_taken(client, &mut taken, &name, &title);
+        }
+    }
+
+    if !warnings.is_empty() {
+        println!("Problems in governments:");
+        for warning in warnings {
+            println!("  {}", warning);
+        }
     }
 }
 
 fn run_map(client: &mut MediaWikiClient) {
-    let super_regions = parse_map();
+    let (super_regions, report) = parse_map_with_geography();
+    report.print();
     let continents = parse_continents();
+    let localisations = parse_all_localisations();
 
     let mut province_list_page = String::new();
     province_list_page.push_str("{| class=\"wikitable sortable\" style=\"font-size:95%; text-align:left\"\n");
@@ -568,19 +1426,8 @@ fn run_map(client: &mut MediaWikiClient) {
             for area in region.areas {
                 for province in area.provinces {
                     let continent = match continents.get(&province.id) {
-                        Some(id) => {
-                            match id.as_str() {
-                                "africa" => "Sarhal",
-                                "europe" => "Cannor",
-                                "serpentspine" => "Serpentspine",
-                                "asia" => "Haless",
-                                "north_america" => "North Aelantir",
-                                "south_america" => "South Aelantir",
-                                "oceania" => "Insyaa",
-                                _ => ""
-                            }
-                        }
-                        None => {""}
+                        Some(id) => continent_name(id, &localisations),
+                        None => String::new()
                     };
                     rows.insert(
                         province.id,
@@ -610,6 +1457,556 @@ fn run_map(client: &mut MediaWikiClient) {
     client.add_edit_page(&"Geographical list of provinces".to_string(), province_list_page);
 }
 
+fn run_map_images(client: &mut MediaWikiClient) {
+    let super_regions = parse_map();
+    let province_map = parse_province_map();
+    let country_colours = parse_all_country_data()
+        .into_iter()
+        .filter_map(|(tag, data)| Some((tag, data.colour?)))
+        .collect::<HashMap<_, _>>();
+    let trade_good_colours = parse_trade_goods()
+        .into_iter()
+        .filter_map(|good| Some((good.id, good.colour?)))
+        .collect::<HashMap<_, _>>();
+    let mut religion_colours: HashMap<String, Colour> = HashMap::new();
+    for group in parse_religious_groups(None) {
+        for religion in group.religions.values() {
+            if let [r, g, b] = religion.color.as_slice() {
+                religion_colours.insert(religion.id.clone(), [*r as u8, *g as u8, *b as u8]);
+            }
+        }
+    }
+
+    let mut states = HashMap::new();
+    let mut super_region_ids = HashMap::new();
+    for super_region in super_regions.iter() {
+        for province in super_region.regions.iter().flat_map(|r| r.areas.iter()).flat_map(|a| a.provinces.iter()) {
+            states.insert(province.id, province.start_state());
+            super_region_ids.insert(province.id, super_region.id.clone());
+        }
+    }
+
+    let out = PathBuf::from("./maps");
+    fs::create_dir_all(&out).expect("could not create the map output directory");
+    let maps: Vec<(&str, Box<dyn Fn(u64) -> Option<Colour>>)> = vec![
+        ("Political map.png", Box::new(|id| {
+            states.get(&id)?.owner.as_ref().and_then(|o| country_colours.get(o)).copied()
+        })),
+        ("Culture map.png", Box::new(|id| states.get(&id)?.culture.as_deref().map(hashed_colour))),
+        ("Religion map.png", Box::new(|id| {
+            states.get(&id)?.religion.as_ref().and_then(|r| religion_colours.get(r)).copied()
+        })),
+        ("Trade goods map.png", Box::new(|id| {
+            states.get(&id)?.trade_goods.as_ref().and_then(|t| trade_good_colours.get(t)).copied()
+        })),
+        ("Development map.png", Box::new(|id| {
+            states.get(&id).filter(|s| s.development() > 0).map(|s| gradient_colour(s.development(), 30))
+        })),
+        ("Superregion map.png", Box::new(|id| super_region_ids.get(&id).map(|sr| hashed_colour(sr)))),
+    ];
+    for (filename, colour) in maps.iter() {
+        if let Some(path) = save_map_image(&province_map.render(colour).downscale(2), &out.join(filename)) {
+            client.upload(filename.to_string(), &path);
+        }
+    }
+
+    let political = &maps[0].1;
+    for id in states.keys() {
+        if let Some(image) = province_map.locator(*id, political, 150) {
+            let filename = Province::locator_file(*id);
+            if let Some(path) = save_map_image(&image, &out.join(&filename)) {
+                client.upload(filename, &path);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+fn save_map_image(image: &Image, path: &Path) -> Option<PathBuf> {
+    match image.save_png(path) {
+        Ok(()) => Some(path.to_path_buf()),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
+}
+
+// Continents are localised by their id, e.g. europe is Cannor
+fn continent_name(id: &str, localisations: &HashMap<String, String>) -> String {
+    match localisations.get(id) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => title_case(id),
+    }
+}
+
+// Province names aren't unique, the duplicates get their id appended
+fn province_titles(super_regions: &Vec<SuperRegion>) -> BTreeMap<u64, String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let provinces = super_regions.iter()
+        .flat_map(|sr| sr.regions.iter())
+        .flat_map(|r| r.areas.iter())
+        .flat_map(|a| a.provinces.iter())
+        .collect::<Vec<_>>();
+    for province in provinces.iter() {
+        *counts.entry(province.page_title()).or_insert(0) += 1;
+    }
+    provinces.iter()
+        .map(|p| {
+            let title = p.page_title();
+            if counts.get(&title).is_some_and(|c| *c > 1) {
+                (p.id, format!("{} ({})", deunicode(p.name.as_str()), p.id))
+            } else {
+                (p.id, title)
+            }
+        })
+        .collect()
+}
+
+fn localised_link(localisations: &HashMap<String, String>, key: &Option<String>) -> String {
+    match key {
+        Some(key) => match localisations.get(key) {
+            Some(name) if !name.is_empty() => format!("[[{}]]", deunicode(name)),
+            _ => key.to_string(),
+        },
+        None => "".to_string(),
+    }
+}
+
+fn run_provinces(client: &mut MediaWikiClient) {
+    let (super_regions, report) = parse_map_with_geography();
+    report.print();
+    let continents = parse_continents();
+    let localisations = parse_all_localisations();
+    let titles = province_titles(&super_regions);
+    let graph = parse_adjacency_graph();
+
+    for super_region in super_regions.iter() {
+        let mut regions = super_region.regions.iter().collect::<Vec<_>>();
+        regions.sort_by(|a, b| a.id.cmp(&b.id));
+        for region in regions {
+            let mut areas = region.areas.iter().collect::<Vec<_>>();
+            areas.sort_by(|a, b| a.id.cmp(&b.id));
+            let mut region_page = format!(
+                "'''{}''' is a region in [[{}]].\n\n{{| class=\"wikitable sortable\"\n|-\n! Area !! Provinces\n",
+                region.name, super_region.name
+            );
+            for area in areas {
+                let mut provinces = area.provinces.iter().collect::<Vec<_>>();
+                provinces.sort_by_key(|p| p.id);
+                let mut area_page = format!(
+                    "'''{}''' is an area in the [[{}]] region.\n\n{{| class=\"wikitable sortable\"\n|-\n! ID !! Province !! Owner !! Development\n",
+                    area.name, region.page_title()
+                );
+                for province in provinces.iter() {
+                    let title = titles.get(&province.id).unwrap();
+                    let state = province.start_state();
+                    area_page += format!(
+                        "|-\n| {} || [[{}|{}]] || {} || {}\n",
+                        province.id, title, province.name, localised_link(&localisations, &state.owner), state.development()
+                    ).as_str();
+
+                    let mut page_str = province_page(province, area, region, super_region, &continents, &localisations, &graph, &titles);
+                    page_str += "\n<noinclude>[[Category:Provinces]]</noinclude>\n";
+                    client.add_edit_page(title, page_str);
+                }
+                area_page += "|}\n\n<noinclude>[[Category:Areas]]</noinclude>\n";
+                client.add_edit_page(&area.page_title(), area_page);
+
+                let province_links = provinces.iter()
+                    .map(|p| format!("[[{}|{}]]", titles.get(&p.id).unwrap(), p.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                region_page += format!("|-\n| [[{}|{}]] || {}\n", area.page_title(), area.name, province_links).as_str();
+            }
+            region_page += "|}\n\n<noinclude>[[Category:Regions]]</noinclude>\n";
+            client.add_edit_page(&region.page_title(), region_page);
+        }
+    }
+}
+
+fn province_page(
+    province: &Province,
+    area: &Area,
+    region: &Region,
+    super_region: &SuperRegion,
+    continents: &BTreeMap<u64, String>,
+    localisations: &HashMap<String, String>,
+    graph: &AdjacencyGraph,
+    titles: &BTreeMap<u64, String>
+) -> String {
+    let continent = continents.get(&province.id).map(|c| continent_name(c, localisations)).unwrap_or_default();
+    let mut page_str = String::new();
+    page_str += "{{Province Detail\n";
+    page_str += format!("|id={}\n|name={}\n|adjective={}\n", province.id, province.name, province.adj).as_str();
+    page_str += format!("|continent={}\n|superregion={}\n", continent, super_region.name).as_str();
+    page_str += format!("|region=[[{}|{}]]\n|area=[[{}|{}]]\n", region.page_title(), region.name, area.page_title(), area.name).as_str();
+    page_str += format!("|locator={}\n", Province::locator_file(province.id)).as_str();
+    let state = province.start_state();
+    page_str += format!("|owner={}\n", localised_link(localisations, &state.owner)).as_str();
+    page_str += format!("|controller={}\n", localised_link(localisations, &state.controller)).as_str();
+    page_str += format!("|culture={}\n", localised_link(localisations, &state.culture)).as_str();
+    page_str += format!("|religion={}\n", localised_link(localisations, &state.religion)).as_str();
+    page_str += format!("|trade_goods={}\n", localised_link(localisations, &state.trade_goods)).as_str();
+    page_str += format!(
+        "|base_tax={}\n|base_production={}\n|base_manpower={}\n|development={}\n",
+        state.base_tax.unwrap_or(0),
+        state.base_production.unwrap_or(0),
+        state.base_manpower.unwrap_or(0),
+        state.development()
+    ).as_str();
+    page_str += format!("|coastal={}\n", if graph.is_coastal(province.id) { "yes" } else { "no" }).as_str();
+    let geography = &province.geography;
+    page_str += format!("|terrain={}\n", localised_link(localisations, &geography.terrain)).as_str();
+    page_str += format!("|climate={}\n", localised_link(localisations, &geography.climate)).as_str();
+    page_str += format!("|winter={}\n", localised_link(localisations, &geography.winter)).as_str();
+    page_str += format!("|monsoon={}\n", localised_link(localisations, &geography.monsoon)).as_str();
+    if geography.impassable {
+        page_str += "|impassable=yes\n";
+    }
+    page_str += "}}\n";
+
+    let neighbours = graph.neighbours(province.id)
+        .iter()
+        .map(|id| {
+            let name = localisations.get(&format!("PROV{id}")).cloned().unwrap_or(id.to_string());
+            match titles.get(id) {
+                Some(title) => format!("[[{}|{}]]", title, name),
+                None => name,
+            }
+        })
+        .collect::<Vec<_>>();
+    if !neighbours.is_empty() {
+        page_str += format!("\n== Neighbours ==\n{}\n", neighbours.join(", ")).as_str();
+    }
+
+    let timeline = province.history.as_ref().map(|h| h.timeline()).unwrap_or_default();
+    if !timeline.is_empty() {
+        page_str += "\n== History ==\n{| class=\"wikitable\"\n|-\n! Date !! Owner !! Culture !! Religion\n";
+        for entry in timeline {
+            page_str += format!(
+                "|-\n| {} || {} || {} || {}\n",
+                entry.date.game_fmt(),
+                localised_link(localisations, &entry.changes.owner),
+                localised_link(localisations, &entry.changes.culture),
+                localised_link(localisations, &entry.changes.religion)
+            ).as_str();
+        }
+        page_str += "|}\n";
+    }
+    page_str
+}
+
+fn trade_node_title(id: &str, localisations: &HashMap<String, String>) -> String {
+    let name = localisations.get(id).filter(|n| !n.is_empty()).cloned().unwrap_or(title_case(id));
+    format!("{} (trade node)", deunicode(name.as_str()))
+}
+
+fn run_trade(client: &mut MediaWikiClient) {
+    let super_regions = parse_map();
+    let localisations = parse_all_localisations();
+    let titles = province_titles(&super_regions);
+    let province_link = |id: &u64| {
+        let name = localisations.get(&format!("PROV{id}")).cloned().unwrap_or(id.to_string());
+        match titles.get(id) {
+            Some(title) => format!("[[{}|{}]]", title, name),
+            None => name,
+        }
+    };
+
+    let nodes = parse_trade_nodes();
+    let mut index_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Trade node !! Location !! Inland !! End node !! Provinces !! Outgoing\n");
+    for node in nodes.iter() {
+        let title = trade_node_title(&node.id, &localisations);
+        let outgoing = node.outgoing.iter()
+            .map(|o| format!("[[{}]]", trade_node_title(o, &localisations)))
+            .collect::<Vec<_>>();
+        let incoming = nodes.iter()
+            .filter(|n| n.outgoing.contains(&node.id))
+            .map(|n| format!("[[{}]]", trade_node_title(&n.id, &localisations)))
+            .collect::<Vec<_>>();
+
+        let mut page_str = format!(
+            "{{{{Trade Node\n|name={}\n|location={}\n|inland={}\n|end={}\n}}}}\n",
+            title.trim_end_matches(" (trade node)"),
+            province_link(&node.location),
+            if node.inland { "yes" } else { "no" },
+            if node.end { "yes" } else { "no" }
+        );
+        if !incoming.is_empty() {
+            page_str += format!("\n== Incoming ==\n* {}\n", incoming.join("\n* ")).as_str();
+        }
+        if !outgoing.is_empty() {
+            page_str += format!("\n== Outgoing ==\n* {}\n", outgoing.join("\n* ")).as_str();
+        }
+        page_str += format!(
+            "\n== Provinces ==\n{}\n\n<noinclude>[[Category:Trade nodes]]</noinclude>\n",
+            node.members.iter().map(province_link).collect::<Vec<_>>().join(", ")
+        ).as_str();
+        client.add_edit_page(&title, page_str);
+
+        index_page += format!(
+            "|-\n| [[{}]] || {} || {} || {} || {} || {}\n",
+            title,
+            province_link(&node.location),
+            if node.inland { "✅" } else { "❌" },
+            if node.end { "✅" } else { "❌" },
+            node.members.len(),
+            outgoing.join(", ")
+        ).as_str();
+    }
+    index_page += "|}\n";
+    client.add_edit_page(&"Trade nodes".to_string(), index_page);
+
+    let mut produced: HashMap<String, usize> = HashMap::new();
+    for province in super_regions.iter()
+        .flat_map(|sr| sr.regions.iter())
+        .flat_map(|r| r.areas.iter())
+        .flat_map(|a| a.provinces.iter()) {
+        if let Some(good) = province.start_state().trade_goods {
+            *produced.entry(good).or_insert(0) += 1;
+        }
+    }
+
+    let mut page_str = String::from("{| class=\"wikitable sortable\"\n|-\n! Trade good !! Base price !! Producing the most !! Province modifiers !! Provinces at start\n");
+    for good in parse_trade_goods() {
+        // unknown is the placeholder for uncolonised provinces
+        if good.id == "unknown" {
+            continue;
+        }
+        let name = localisations.get(&good.id).cloned().unwrap_or(title_case(&good.id));
+        let modifiers = format_modifiers(&good.modifiers).iter()
+            .map(|m| m.to_template(false))
+            .collect::<Vec<_>>()
+            .join("<br />");
+        let province_modifiers = format_modifiers(&good.province_modifiers).iter()
+            .map(|m| m.to_template(false))
+            .collect::<Vec<_>>()
+            .join("<br />");
+        page_str += format!(
+            "|-\n| {} || {} || {} || {} || {}\n",
+            deunicode(name.as_str()),
+            good.base_price.map(|p| format!("{:.2}", p)).unwrap_or_default(),
+            modifiers,
+            province_modifiers,
+            produced.get(&good.id).unwrap_or(&0)
+        ).as_str();
+    }
+    page_str += "|}\n";
+    client.add_edit_page(&"Trade goods".to_string(), page_str);
+}
+
+fn culture_group_title(group: &CultureGroup, localisations: &HashMap<String, String>) -> String {
+    let name = localisations.get(&group.id).filter(|n| !n.is_empty()).cloned().unwrap_or(title_case(&group.id));
+    format!("{} (culture group)", deunicode(name.as_str()))
+}
+
+fn run_cultures(client: &mut MediaWikiClient) {
+    let localisations = parse_all_localisations();
+    let mut groups = parse_cultures();
+    let timelines = parse_country_timelines();
+    assign_races(&mut groups, &timelines);
+    let races = parse_races(&groups);
+    let super_regions = parse_map();
+    let titles = province_titles(&super_regions);
+    let mut taken = taken_titles(&localisations, Redirects::Cultures);
+
+    let mut countries: HashMap<String, Vec<String>> = HashMap::new();
+    for (tag, timeline) in timelines.iter() {
+        if let Some(name) = localisations.get(tag).filter(|n| !n.is_empty()) {
+            let culture = timeline.state_at(start_date()).primary_culture;
+            countries.entry(culture).or_default().push(deunicode(name));
+        }
+    }
+    let mut provinces: HashMap<String, Vec<(u64, String)>> = HashMap::new();
+    for province in super_regions.iter()
+        .flat_map(|sr| sr.regions.iter())
+        .flat_map(|r| r.areas.iter())
+        .flat_map(|a| a.provinces.iter()) {
+        if let Some(culture) = province.start_state().culture {
+            let title = titles.get(&province.id).cloned().unwrap_or_default();
+            provinces.entry(culture).or_default().push((province.id, format!("[[{}|{}]]", title, province.name)));
+        }
+    }
+
+    let mut index_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Culture group !! Cultures !! Graphical culture\n");
+    for group in groups.iter() {
+        let title = culture_group_title(group, &localisations);
+        let race = races.iter()
+            .find(|r| r.culture_groups.contains(&group.id))
+            .map(|r| format!("[[{}]]", race_title(&r.id)))
+            .unwrap_or_default();
+        let mut page_str = format!(
+            "{{{{Culture Group\n|name={}\n|race={}\n|graphical_culture={}\n|second_graphical_culture={}\n}}}}\n",
+            title.trim_end_matches(" (culture group)"),
+            race,
+            group.graphical_culture.as_deref().unwrap_or(""),
+            group.second_graphical_culture.as_deref().unwrap_or("")
+        );
+        if !group.country_modifiers.is_empty() {
+            page_str += "\nCountries with a culture of this group as primary culture receive:\n";
+            add_modifiers(&mut page_str, &group.country_modifiers);
+        }
+        if !group.province_modifiers.is_empty() {
+            page_str += "\nProvinces with a culture of this group receive:\n";
+            add_modifiers(&mut page_str, &group.province_modifiers);
+        }
+
+        for culture in group.cultures.iter() {
+            let name = localised_name(&localisations, &culture.id);
+            page_str += format!("\n== {} ==\n", name).as_str();
+            if let Some(primary) = culture.primary.as_ref().and_then(|tag| localisations.get(tag)) {
+                page_str += format!("Primary nation: [[{}]]\n\n", deunicode(primary)).as_str();
+            }
+            if !culture.country_modifiers.is_empty() {
+                page_str += format!("{} countries receive:\n", name).as_str();
+                add_modifiers(&mut page_str, &culture.country_modifiers);
+            }
+            if !culture.province_modifiers.is_empty() {
+                page_str += format!("{} provinces receive:\n", name).as_str();
+                add_modifiers(&mut page_str, &culture.province_modifiers);
+            }
+            if let Some(countries) = countries.get_mut(&culture.id) {
+                countries.sort();
+                page_str += format!(
+                    "\n'''Countries at the start:''' {}\n",
+                    countries.iter().map(|c| format!("[[{}]]", c)).collect::<Vec<_>>().join(", ")
+                ).as_str();
+            }
+            if let Some(provinces) = provinces.get_mut(&culture.id) {
+                provinces.sort();
+                page_str += format!(
+                    "\n'''Provinces at the start ({}):''' {}\n",
+                    provinces.len(),
+                    provinces.iter().map(|(_, link)| link.as_str()).collect::<Vec<_>>().join(", ")
+                ).as_str();
+            }
+            let male_names = if culture.male_names.is_empty() { &group.male_names } else { &culture.male_names };
+            let female_names = if culture.female_names.is_empty() { &group.female_names } else { &culture.female_names };
+            if !male_names.is_empty() {
+                page_str += format!("\n'''Male names:''' {}\n", deunicode(male_names.join(", ").as_str())).as_str();
+            }
+            if !female_names.is_empty() {
+                page_str += format!("\n'''Female names:''' {}\n", deunicode(female_names.join(", ").as_str())).as_str();
+            }
+
+            // the Countries table links to the culture name
+            redirect_unless_taken(client, &mut taken, &name, &format!("{}#{}", title, name));
+        }
+        page_str += "\n<noinclude>[[Category:Culture groups]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+
+        index_page += format!(
+            "|-\n| [[{}]] || {} || {}\n",
+            title,
+            group.cultures.len(),
+            group.graphical_culture.as_deref().unwrap_or("")
+        ).as_str();
+    }
+    index_page += "|}\n";
+    client.add_edit_page(&"Cultures".to_string(), index_page);
+}
+
+fn great_project_tiers(project: &GreatProject) -> Vec<(u64, &Tier)> {
+    [(1, &project.tier_1), (2, &project.tier_2), (3, &project.tier_3)]
+        .into_iter()
+        .filter_map(|(level, tier)| tier.as_ref().map(|tier| (level, tier)))
+        .collect()
+}
+
+// Who receives a tier's modifiers, for the project page and the overview table
+fn great_project_modifiers(tier: &Tier) -> [(&'static str, &BTreeMap<String, Vec<u8>>); 4] {
+    [
+        ("The province", &tier.province_modifiers),
+        ("The area", &tier.area_modifier),
+        ("The region", &tier.region_modifier),
+        ("The owner", &tier.country_modifiers),
+    ]
+}
+
+fn run_great_projects(client: &mut MediaWikiClient) {
+    let localisations = parse_all_localisations();
+    let mut projects = parse_all_great_projects();
+    projects.sort_by(|a, b| a.id.cmp(&b.id));
+    let super_regions = parse_map();
+    let titles = province_titles(&super_regions);
+    let province_link = |id: &Option<u64>| match id {
+        Some(id) => match titles.get(id) {
+            Some(title) => format!("[[{}|{}]]", title, localised_name(&localisations, &format!("PROV{}", id))),
+            None => format!("Province {}", id),
+        },
+        None => String::new(),
+    };
+
+    let mut index_page = String::from("{| class=\"wikitable sortable\"\n|-\n! Great project !! Location !! Type !! Starting tier !! Upgrade times (months) !! Upgrade costs !! Modifiers\n");
+    for project in projects.iter() {
+        let name = localised_name(&localisations, &project.id);
+        let tiers = great_project_tiers(project);
+        let mut page_str = format!(
+            "{{{{Great Project\n|name={}\n|location={}\n|type={}\n|starting_tier={}\n}}}}\n",
+            name,
+            province_link(&project.start),
+            title_case(&project.project_type),
+            project.starting_tier.unwrap_or(0)
+        );
+        if let Some(desc) = localisations.get(&format!("{}_desc", project.id)) {
+            page_str += format!("\n{}\n", desc.replace("\\n", "<br>")).as_str();
+        }
+        for (level, tier) in tiers.iter() {
+            page_str += format!("\n== Tier {} ==\n", level).as_str();
+            if let Some(months) = tier.upgrade_time {
+                page_str += format!("Upgrade time: {} months\n\n", months).as_str();
+            }
+            if let Some(cost) = tier.cost_to_upgrade {
+                page_str += format!("Upgrade cost: {}\n\n", cost).as_str();
+            }
+            for (heading, modifiers) in great_project_modifiers(tier) {
+                if !modifiers.is_empty() {
+                    page_str += format!("{} receives:\n", heading).as_str();
+                    add_modifiers(&mut page_str, modifiers);
+                    page_str += "\n";
+                }
+            }
+            if let Some(value) = tier.on_upgraded.as_ref().and_then(|e| serde_json::from_str::<Value>(e.as_str()).ok()) {
+                page_str += format!("When upgraded to this tier:\n{}\n", htmlify(&value)).as_str();
+            }
+        }
+        page_str += "\n<noinclude>[[Category:Great projects]]</noinclude>\n";
+        client.add_edit_page(&name, page_str);
+
+        let column = |value: fn(&Tier) -> Option<u64>| tiers.iter()
+            .map(|(level, tier)| format!("{}: {}", level, value(tier).map(|v| v.to_string()).unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join("<br>");
+        let mut modifiers = String::new();
+        for (level, tier) in tiers.iter() {
+            let sections = great_project_modifiers(tier);
+            if sections.iter().all(|(_, modifiers)| modifiers.is_empty()) {
+                continue;
+            }
+            modifiers += format!("\n'''Tier {}'''\n", level).as_str();
+            for (heading, tier_modifiers) in sections {
+                if !tier_modifiers.is_empty() {
+                    modifiers += format!("{}:\n", heading).as_str();
+                    add_modifiers(&mut modifiers, tier_modifiers);
+                }
+            }
+        }
+        index_page += format!(
+            "|-\n| [[{}]] || {} || {} || {} || {} || {} ||{}\n",
+            name,
+            province_link(&project.start),
+            title_case(&project.project_type),
+            project.starting_tier.unwrap_or(0),
+            column(|tier| tier.upgrade_time),
+            column(|tier| tier.cost_to_upgrade),
+            modifiers.trim_end()
+        ).as_str();
+    }
+    index_page += "|}\n";
+    client.add_edit_page(&"Great projects".to_string(), index_page);
+}
+
 fn run_bundled_modifiers(client: &mut MediaWikiClient) {
     let localisations = parse_all_localisations();
     let icons = parse_all_icons();
@@ -670,7 +2067,57 @@ fn run_bundled_modifiers(client: &mut MediaWikiClient) {
     println!("ICONLESS {}", iconless);
 }
 
-fn run_modifiers(client: &mut MediaWikiClient) {}
+fn run_modifiers(client: &mut MediaWikiClient) {
+    let localisations = parse_all_localisations();
+    let index = parse_modifier_uses(&localisations);
+    let source_types = [
+        ModifierSourceType::Idea,
+        ModifierSourceType::GovernmentReform,
+        ModifierSourceType::Religion,
+        ModifierSourceType::ReligiousSchool,
+        ModifierSourceType::Icon,
+        ModifierSourceType::GreatProject,
+        ModifierSourceType::BundledModifier,
+        ModifierSourceType::Policy,
+    ];
+
+    let mut master_page = String::new();
+    master_page += "{| class=\"wikitable sortable\" style=\"text-align: center;\"\n";
+    master_page += "|-\n! Modifier";
+    for source_type in source_types.iter() {
+        master_page += format!(" !! {}", source_type.as_str()).as_str();
+    }
+    master_page += " !! Total\n";
+
+    for uses in index.used() {
+        let title = uses.page_title();
+        let mut page_str = String::new();
+        page_str += "{| class=\"wikitable sortable\"\n";
+        page_str += "|-\n! Source !! Type !! Value\n";
+        let mut sorted = uses.uses.clone();
+        sorted.sort_by(|a, b| a.source_type.cmp(&b.source_type).then(a.source.cmp(&b.source)));
+        for source in sorted {
+            if let Some(formatted) = format_modifier(uses.modifier.id, source.value.as_slice()) {
+                page_str += format!(
+                    "|-\n| {} || {} || {}\n",
+                    source.source.replace("|", "<nowiki>|</nowiki>"),
+                    source.source_type.as_str(),
+                    formatted.to_template(false)
+                ).as_str();
+            }
+        }
+        page_str += "|}\n\n<noinclude>[[Category:Modifier sources]]</noinclude>\n";
+        client.add_edit_page(&title, page_str);
+
+        master_page += format!("|-\n| [[{}|{}]]", title, uses.display_name()).as_str();
+        for source_type in source_types.iter() {
+            master_page += format!(" || {}", uses.count(*source_type)).as_str();
+        }
+        master_page += format!(" || {}\n", uses.uses.len()).as_str();
+    }
+    master_page += "|}\n";
+    client.add_edit_page(&"Modifier sources".to_string(), master_page);
+}
 
 fn run_missions(client: &mut MediaWikiClient) {
     let localisations = parse_all_localisations();
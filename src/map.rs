@@ -3,21 +3,42 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::hash::{Hash, Hasher};
 
-use jomini::{JominiDeserialize, TextTape};
+use deunicode::deunicode;
+use jomini::common::Date;
+use jomini::text::ValueReader;
+use jomini::{TextTape, Windows1252Encoding};
 
 use crate::localisation::parse_all_localisations;
+use crate::provincemap::{read_indexed_bmp, read_province_map};
+use crate::utils::start_date;
 
 #[derive(Debug, Eq)]
 pub struct Province {
     pub id: u64,
     pub name: String,
     pub adj: String,
-    pub history: Option<ProvinceHistory>
+    pub history: Option<ProvinceHistory>,
+    pub geography: Geography,
     // pub owner: String,
     // pub culture: String,
     // pub religion: String,
 }
 
+impl Province {
+    pub fn page_title(&self) -> String {
+        format!("{} (province)", deunicode(self.name.as_str()))
+    }
+
+    // Uploaded by --map-images
+    pub fn locator_file(id: u64) -> String {
+        format!("Province {} locator.png", id)
+    }
+
+    pub fn start_state(&self) -> ProvinceState {
+        self.history.as_ref().map(|h| h.state_at(start_date())).unwrap_or_default()
+    }
+}
+
 impl PartialEq for Province {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -36,10 +57,10 @@ impl Borrow<u64> for Province {
     }
 }
 
-#[derive(Debug, JominiDeserialize, Eq, PartialEq)]
-#[derive(Clone)]
-pub struct ProvinceHistory {
-    #[jomini(take_last)]
+// The province as it is at one point in time, every field is optional because the dated
+// blocks only name what changes
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct ProvinceState {
     pub owner: Option<String>,
     pub controller: Option<String>,
     pub culture: Option<String>,
@@ -47,11 +68,100 @@ pub struct ProvinceHistory {
     pub base_tax: Option<u64>,
     pub base_production: Option<u64>,
     pub base_manpower: Option<u64>,
-    #[jomini(take_last)]
     pub trade_goods: Option<String>,
     pub is_city: Option<bool>
 }
 
+impl ProvinceState {
+    pub fn development(&self) -> u64 {
+        self.base_tax.unwrap_or(0) + self.base_production.unwrap_or(0) + self.base_manpower.unwrap_or(0)
+    }
+
+    // Overwrites every field the other state sets
+    pub fn apply(&mut self, other: &ProvinceState) {
+        if other.owner.is_some() { self.owner = other.owner.clone(); }
+        if other.controller.is_some() { self.controller = other.controller.clone(); }
+        if other.culture.is_some() { self.culture = other.culture.clone(); }
+        if other.religion.is_some() { self.religion = other.religion.clone(); }
+        if other.base_tax.is_some() { self.base_tax = other.base_tax; }
+        if other.base_production.is_some() { self.base_production = other.base_production; }
+        if other.base_manpower.is_some() { self.base_manpower = other.base_manpower; }
+        if other.trade_goods.is_some() { self.trade_goods = other.trade_goods.clone(); }
+        if other.is_city.is_some() { self.is_city = other.is_city; }
+    }
+
+    // Returns false for fields that aren't part of the state
+    fn set(&mut self, key: &str, value: &ValueReader<Windows1252Encoding>) -> bool {
+        match key {
+            "owner" => self.owner = value.read_string().ok(),
+            "controller" => self.controller = value.read_string().ok(),
+            "culture" => self.culture = value.read_string().ok(),
+            "religion" => self.religion = value.read_string().ok(),
+            "trade_goods" => self.trade_goods = value.read_string().ok(),
+            "base_tax" => self.base_tax = value.read_scalar().ok().and_then(|v| v.to_u64().ok()),
+            "base_production" => self.base_production = value.read_scalar().ok().and_then(|v| v.to_u64().ok()),
+            "base_manpower" => self.base_manpower = value.read_scalar().ok().and_then(|v| v.to_u64().ok()),
+            "is_city" => self.is_city = value.read_scalar().ok().and_then(|v| v.to_bool().ok()),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ProvinceHistoryEntry {
+    pub date: Date,
+    pub changes: ProvinceState,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct ProvinceHistory {
+    // Everything outside of a dated block
+    pub base: ProvinceState,
+    // Sorted by date, blocks with the same date stay in file order
+    pub entries: Vec<ProvinceHistoryEntry>,
+}
+
+impl ProvinceHistory {
+    pub fn state_at(&self, date: Date) -> ProvinceState {
+        let mut state = self.base.clone();
+        for entry in self.entries.iter().take_while(|e| e.date <= date) {
+            state.apply(&entry.changes);
+        }
+        state
+    }
+
+    // Dated changes to owner, culture or religion, the rest isn't interesting enough for a timeline
+    pub fn timeline(&self) -> Vec<&ProvinceHistoryEntry> {
+        self.entries.iter()
+            .filter(|e| e.changes.owner.is_some() || e.changes.culture.is_some() || e.changes.religion.is_some())
+            .collect()
+    }
+}
+
+pub fn parse_province_history(data: &[u8]) -> Result<ProvinceHistory, String> {
+    let mut history = ProvinceHistory::default();
+    let tape = TextTape::from_slice(data).map_err(|e| e.to_string())?;
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let key = key.read_str();
+        if let Ok(date) = Date::parse(key.as_ref()) {
+            if let Ok(block) = value.read_object() {
+                let mut changes = ProvinceState::default();
+                for (key, _op, value) in block.fields() {
+                    changes.set(key.read_str().as_ref(), &value);
+                }
+                history.entries.push(ProvinceHistoryEntry{ date, changes });
+            }
+        } else {
+            history.base.set(key.as_ref(), &value);
+        }
+    }
+    // stable, so same day entries keep their order
+    history.entries.sort_by_key(|e| e.date);
+    Ok(history)
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Area {
     pub id: String,
@@ -60,6 +170,12 @@ pub struct Area {
 }
 impl Eq for Area {}
 
+impl Area {
+    pub fn page_title(&self) -> String {
+        format!("{} (area)", deunicode(self.name.as_str()))
+    }
+}
+
 impl Hash for Area {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.id.hash(state);
@@ -79,6 +195,12 @@ pub struct Region {
     pub areas: HashSet<Area>
 }
 
+impl Region {
+    pub fn page_title(&self) -> String {
+        format!("{} (region)", deunicode(self.name.as_str()))
+    }
+}
+
 impl PartialEq for Region {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -105,6 +227,171 @@ pub struct SuperRegion {
     pub restrict_charter: bool
 }
 
+#[derive(Debug, Default)]
+pub struct DefaultMap {
+    pub sea_starts: Vec<u64>,
+    pub lakes: Vec<u64>,
+}
+
+fn read_ids(value: &ValueReader<Windows1252Encoding>) -> Vec<u64> {
+    match value.read_array() {
+        Ok(ids) => ids.values().filter_map(|v| v.read_scalar().ok()?.to_u64().ok()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+pub fn parse_default_map() -> DefaultMap {
+    let file = fs::read("./anbennar/map/default.map").expect("error reading file");
+    let tape = TextTape::from_slice(file.as_slice()).unwrap();
+    let reader = tape.windows1252_reader();
+    let mut default_map = DefaultMap::default();
+    for (key, _op, value) in reader.fields() {
+        match key.read_str().as_ref() {
+            "sea_starts" => default_map.sea_starts.extend(read_ids(&value)),
+            "lakes" => default_map.lakes.extend(read_ids(&value)),
+            _ => {}
+        }
+    }
+    default_map
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Geography {
+    pub terrain: Option<String>,
+    // tropical, arid or arctic
+    pub climate: Option<String>,
+    // mild_winter, normal_winter or severe_winter
+    pub winter: Option<String>,
+    // mild_monsoon, normal_monsoon or severe_monsoon
+    pub monsoon: Option<String>,
+    // wastelands
+    pub impassable: bool,
+    pub sea: bool,
+    pub lake: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Terrain {
+    // terrain.bmp palette index -> category
+    pub indices: BTreeMap<u8, String>,
+    // province -> category from terrain_override
+    pub overrides: BTreeMap<u64, String>,
+}
+
+pub fn parse_terrain(data: &[u8]) -> Result<Terrain, String> {
+    let tape = TextTape::from_slice(data).map_err(|e| e.to_string())?;
+    let reader = tape.windows1252_reader();
+    let mut terrain = Terrain::default();
+    for (key, _op, value) in reader.fields() {
+        let Ok(value) = value.read_object() else { continue };
+        match key.read_str().as_ref() {
+            "categories" => {
+                for (category, _op, value) in value.fields() {
+                    let Ok(value) = value.read_object() else { continue };
+                    for (k, _op, v) in value.fields() {
+                        if k.read_str() == "terrain_override" {
+                            for id in read_ids(&v) {
+                                terrain.overrides.insert(id, category.read_string());
+                            }
+                        }
+                    }
+                }
+            }
+            "terrain" => {
+                for (_key, _op, value) in value.fields() {
+                    let Ok(value) = value.read_object() else { continue };
+                    let mut category = None;
+                    let mut indices = vec![];
+                    for (k, _op, v) in value.fields() {
+                        match k.read_str().as_ref() {
+                            "type" => category = v.read_string().ok(),
+                            "color" => indices = read_ids(&v),
+                            _ => {}
+                        }
+                    }
+                    if let Some(category) = category {
+                        for index in indices {
+                            terrain.indices.insert(index as u8, category.clone());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(terrain)
+}
+
+// climate.txt lists provinces per climate, winter and monsoon
+pub fn parse_climate(data: &[u8]) -> Result<BTreeMap<u64, Geography>, String> {
+    let tape = TextTape::from_slice(data).map_err(|e| e.to_string())?;
+    let reader = tape.windows1252_reader();
+    let mut geography: BTreeMap<u64, Geography> = BTreeMap::new();
+    for (key, _op, value) in reader.fields() {
+        let key = key.read_string();
+        for id in read_ids(&value) {
+            let province = geography.entry(id).or_default();
+            match key.as_str() {
+                "tropical" | "arid" | "arctic" => province.climate = Some(key.clone()),
+                "mild_winter" | "normal_winter" | "severe_winter" => province.winter = Some(key.clone()),
+                "mild_monsoon" | "normal_monsoon" | "severe_monsoon" => province.monsoon = Some(key.clone()),
+                "impassable" => province.impassable = true,
+                _ => {}
+            }
+        }
+    }
+    Ok(geography)
+}
+
+// Seas and lakes from default.map, all the map itself needs to know
+fn parse_water() -> BTreeMap<u64, Geography> {
+    let default_map = parse_default_map();
+    let mut geography: BTreeMap<u64, Geography> = BTreeMap::new();
+    for id in default_map.sea_starts {
+        geography.entry(id).or_default().sea = true;
+    }
+    for id in default_map.lakes {
+        geography.entry(id).or_default().lake = true;
+    }
+    geography
+}
+
+// Terrain comes from terrain_override or else from whatever covers most of the province in terrain.bmp
+pub fn parse_geography() -> Result<BTreeMap<u64, Geography>, MapError> {
+    let error = |file: &'static str| move |reason: String| MapError{ file: format!("map/{}", file), reason };
+    let read = |file: &'static str| fs::read(format!("./anbennar/map/{}", file)).map_err(|e| error(file)(e.to_string()));
+    let mut geography = parse_climate(read("climate.txt")?.as_slice()).map_err(error("climate.txt"))?;
+    let terrain = parse_terrain(read("terrain.txt")?.as_slice()).map_err(error("terrain.txt"))?;
+    let (width, _height, indices) = read_indexed_bmp(read("terrain.bmp")?.as_slice()).map_err(error("terrain.bmp"))?;
+    let province_map = read_province_map().map_err(error("provinces.bmp"))?;
+
+    let mut counts: BTreeMap<u64, BTreeMap<&String, usize>> = BTreeMap::new();
+    for (i, index) in indices.iter().enumerate() {
+        let (x, y) = (i % width, i / width);
+        if x >= province_map.width || y >= province_map.height {
+            continue;
+        }
+        if let Some(category) = terrain.indices.get(index) {
+            *counts.entry(province_map.id_at(x, y)).or_default().entry(category).or_insert(0) += 1;
+        }
+    }
+    for (id, categories) in counts {
+        if let Some((category, _)) = categories.iter().max_by_key(|(_, count)| **count) {
+            geography.entry(id).or_default().terrain = Some(category.to_string());
+        }
+    }
+    for (id, category) in terrain.overrides {
+        geography.entry(id).or_default().terrain = Some(category);
+    }
+
+    for (id, water) in parse_water() {
+        let province = geography.entry(id).or_default();
+        province.sea = water.sea;
+        province.lake = water.lake;
+    }
+    Ok(geography)
+}
+
 pub fn parse_continents() -> BTreeMap<u64, String> {
     let file = fs::read("./anbennar/map/continent.txt").expect("error reading file");
     let tape = TextTape::from_slice(file.as_slice()).unwrap();
@@ -131,36 +418,166 @@ pub fn parse_continents_inverse() -> HashMap<String, Vec<u64>> {
     data
 }
 
+// A file that couldn't be read, the loaders skip it and carry on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapError {
+    pub file: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file, self.reason)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MapReport {
+    pub errors: Vec<MapError>,
+    // land provinces without a history/provinces file
+    pub missing_history: Vec<u64>,
+    pub areas_without_region: Vec<String>,
+    pub regions_without_super_region: Vec<String>,
+    // regions none of whose areas exist
+    pub empty_regions: Vec<String>,
+}
+
+impl MapReport {
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+            && self.missing_history.is_empty()
+            && self.areas_without_region.is_empty()
+            && self.regions_without_super_region.is_empty()
+            && self.empty_regions.is_empty()
+    }
+
+    pub fn print(&self) {
+        for error in self.errors.iter() {
+            println!("Error: {}", error);
+        }
+        if !self.missing_history.is_empty() {
+            println!("Provinces without history: {:?}", self.missing_history);
+        }
+        if !self.areas_without_region.is_empty() {
+            println!("Areas not in any region: {:?}", self.areas_without_region);
+        }
+        if !self.regions_without_super_region.is_empty() {
+            println!("Regions not in any superregion: {:?}", self.regions_without_super_region);
+        }
+        if !self.empty_regions.is_empty() {
+            println!("Regions without areas: {:?}", self.empty_regions);
+        }
+    }
+}
+
 pub fn parse_map() -> Vec<SuperRegion> {
+    parse_map_with_report().0
+}
+
+pub fn parse_map_with_report() -> (Vec<SuperRegion>, MapReport) {
+    build_map(parse_water(), vec![])
+}
+
+// Also fills in terrain and climate, which means decoding terrain.bmp and provinces.bmp, so only
+// the generators that show them use this
+pub fn parse_map_with_geography() -> (Vec<SuperRegion>, MapReport) {
+    match parse_geography() {
+        Ok(geography) => build_map(geography, vec![]),
+        Err(error) => build_map(parse_water(), vec![error]),
+    }
+}
+
+fn build_map(geography: BTreeMap<u64, Geography>, mut errors: Vec<MapError>) -> (Vec<SuperRegion>, MapReport) {
     // Continent > Super region (subcontinent) > Region > Area > Province
     
     let localisations = parse_all_localisations();
-    let histories = parse_province_histories();
+    let (histories, history_errors) = parse_province_histories();
+    errors.extend(history_errors);
+    let mut report = MapReport { errors, ..Default::default() };
+
+    let read = |file: &str| fs::read(format!("./anbennar/map/{}", file))
+        .map_err(|e| MapError{ file: format!("map/{}", file), reason: e.to_string() });
+    let files = read("area.txt").and_then(|area| Ok((area, read("region.txt")?, read("superregion.txt")?)));
+    let super_regions = match files {
+        Ok((area, region, superregion)) => {
+            let sources = MapSources{ localisations: &localisations, histories: &histories, geography: &geography };
+            assemble_map(&sources, &area, &region, &superregion, &mut report)
+        }
+        Err(error) => {
+            report.errors.push(error);
+            vec![]
+        }
+    };
+    (super_regions, report)
+}
+
+// What the provinces are filled in with
+struct MapSources<'a> {
+    localisations: &'a HashMap<String, String>,
+    histories: &'a BTreeMap<u64, ProvinceHistory>,
+    geography: &'a BTreeMap<u64, Geography>,
+}
+
+fn parse_map_file<'a>(file: &str, data: &'a [u8]) -> Result<TextTape<'a>, MapError> {
+    TextTape::from_slice(data).map_err(|e| MapError{ file: format!("map/{}", file), reason: e.to_string() })
+}
+
+// Area, region and superregion files in, the nested map out. A file that doesn't parse leaves
+// the map empty, everything else goes to the report and gets skipped
+fn assemble_map(sources: &MapSources, area_data: &[u8], region_data: &[u8], superregion_data: &[u8], report: &mut MapReport) -> Vec<SuperRegion> {
+    let localisation = |key: String| sources.localisations.get(&key).cloned().unwrap_or_default();
+    let tapes = parse_map_file("area.txt", area_data).and_then(|area| Ok((
+        area,
+        parse_map_file("region.txt", region_data)?,
+        parse_map_file("superregion.txt", superregion_data)?,
+    )));
+    let (area_tape, region_tape, superregion_tape) = match tapes {
+        Ok(tapes) => tapes,
+        Err(error) => {
+            report.errors.push(error);
+            return vec![];
+        }
+    };
 
     // AREAS & PROVINCES
-    let file = fs::read("./anbennar/map/area.txt").expect("error reading file");
-    let tape = TextTape::from_slice(file.as_slice()).unwrap();
-    let reader = tape.windows1252_reader();
+    let reader = area_tape.windows1252_reader();
     let mut areas = HashSet::new();
+    let mut province_areas: HashMap<u64, String> = HashMap::new();
     for (area_id, _op, value) in reader.fields() {
         let area_id_str = area_id.read_str();
-        let name = localisations.get(&format!("{area_id_str}_name")).unwrap_or(&"".to_string()).clone();
         let mut area = Area{
             id: area_id.read_string(),
-            name,
+            name: localisation(format!("{area_id_str}_name")),
             provinces: Default::default(),
         };
         if let Ok(area_provinces) = value.read_array() {
             for province in area_provinces.values() {
-                let id = province.read_scalar().unwrap().to_u64().unwrap();
-                let name = localisations.get(&format!("PROV{id}")).unwrap_or(&"".to_string()).clone();
-                let adj = localisations.get(&format!("PROV_ADJ{id}")).unwrap_or(&"".to_string()).clone();
-                let history = histories.get(&id);
+                let Some(id) = province.read_scalar().ok().and_then(|v| v.to_u64().ok()) else {
+                    report.errors.push(MapError{
+                        file: "map/area.txt".to_string(),
+                        reason: format!("invalid province id in {}", area.id),
+                    });
+                    continue;
+                };
+                if let Some(other) = province_areas.insert(id, area.id.clone()) {
+                    report.errors.push(MapError{
+                        file: "map/area.txt".to_string(),
+                        reason: format!("province {} is in both {} and {}", id, other, area.id),
+                    });
+                    province_areas.insert(id, other);
+                    continue;
+                }
+                let history = sources.histories.get(&id);
+                let geography = sources.geography.get(&id).cloned().unwrap_or_default();
+                if history.is_none() && !geography.sea && !geography.lake {
+                    report.missing_history.push(id);
+                }
                 let p = Province{
                     id,
-                    name,
-                    adj,
+                    name: localisation(format!("PROV{id}")),
+                    adj: localisation(format!("PROV_ADJ{id}")),
                     history: history.cloned(),
+                    geography,
                 };
                 area.provinces.insert(p);
             }
@@ -174,15 +591,13 @@ pub fn parse_map() -> Vec<SuperRegion> {
     }
 
     // REGIONS
-    let file = fs::read("./anbennar/map/region.txt").expect("error reading file");
-    let tape = TextTape::from_slice(file.as_slice()).unwrap();
-    let reader = tape.windows1252_reader();
+    let reader = region_tape.windows1252_reader();
     let mut regions = HashSet::new();
     for (region_id, _op, value) in reader.fields() {
         let region_id = region_id.read_string();
         let mut region = Region{
             id: region_id.clone(),
-            name: localisations.get(&format!("{region_id}_name")).unwrap_or(&"".to_string()).clone(),
+            name: localisation(format!("{region_id}_name")),
             areas: Default::default(),
         };
         if let Ok(r) = value.read_object() {
@@ -190,10 +605,16 @@ pub fn parse_map() -> Vec<SuperRegion> {
                 if k.read_str() == "areas" {
                     if let Ok(region_areas) = v.read_array() {
                         for area in region_areas.values() {
-                            region.areas.insert(areas.take(&area.read_string().unwrap()).unwrap());
-                            // if areas.take(&area.read_string().unwrap()).is_none() {
-                            //     println!("{:?}", area.read_string());
-                            // }
+                            let area_id = area.read_string().unwrap_or_default();
+                            match areas.take(&area_id) {
+                                Some(area) => {
+                                    region.areas.insert(area);
+                                }
+                                None => report.errors.push(MapError{
+                                    file: "map/region.txt".to_string(),
+                                    reason: format!("unknown or repeated area {} in {}", area_id, region_id),
+                                }),
+                            }
                         }
                     }
                 }
@@ -201,30 +622,38 @@ pub fn parse_map() -> Vec<SuperRegion> {
         }
         if region.areas.len() > 0 {
             regions.insert(region);
+        } else {
+            report.empty_regions.push(region_id);
         }
     }
 
     // SUPER REGIONS
-    let file = fs::read("./anbennar/map/superregion.txt").expect("error reading file");
-    let tape = TextTape::from_slice(file.as_slice()).unwrap();
-    let reader = tape.windows1252_reader();
+    let reader = superregion_tape.windows1252_reader();
     let mut super_regions = vec![];
     for (sr_id, _op, value) in reader.fields() {
         let sr_id = sr_id.read_string();
         let mut super_region = SuperRegion{
             id: sr_id.clone(),
-            name: localisations.get(&format!("{sr_id}")).unwrap_or(&"".to_string()).clone(),
+            name: localisation(sr_id.clone()),
             regions: Default::default(),
             restrict_charter: false,
         };
 
         if let Ok(sr_regions) = value.read_array() {
             for region in sr_regions.values() {
-                let region_id = region.read_str().unwrap();
+                let region_id = region.read_string().unwrap_or_default();
                 if region_id == "restrict_charter" {
                     super_region.restrict_charter = true;
-                } else {
-                    super_region.regions.insert(regions.take(&region_id.to_string()).unwrap());
+                } else if !report.empty_regions.contains(&region_id) {
+                    match regions.take(&region_id) {
+                        Some(region) => {
+                            super_region.regions.insert(region);
+                        }
+                        None => report.errors.push(MapError{
+                            file: "map/superregion.txt".to_string(),
+                            reason: format!("unknown or repeated region {} in {}", region_id, sr_id),
+                        }),
+                    }
                 }
             }
         }
@@ -235,32 +664,51 @@ pub fn parse_map() -> Vec<SuperRegion> {
         }
     }
 
+    // empty areas are deprecated ones that were left in the file
+    report.areas_without_region = areas.iter()
+        .filter(|a| !a.provinces.is_empty())
+        .map(|a| a.id.clone())
+        .collect();
+    report.areas_without_region.sort();
+    report.regions_without_super_region = regions.iter().map(|r| r.id.clone()).collect();
+    report.regions_without_super_region.sort();
+    report.empty_regions.sort();
+    report.missing_history.sort();
+
     super_regions
 }
 
-pub fn parse_province_histories() -> BTreeMap<u64, ProvinceHistory> {
+pub fn parse_province_histories() -> (BTreeMap<u64, ProvinceHistory>, Vec<MapError>) {
     let mut histories = BTreeMap::new();
+    let mut errors = vec![];
     let paths = fs::read_dir("./anbennar/history/provinces")
         .expect("Missing province history directory");
-    for path in paths {
-        match path {
-            Ok(file) => {
-                let file_name = file.path();
-                let mut name = file_name.file_stem().unwrap().to_str().unwrap();
-                if name.contains('-') {
-                    name = name.split('-').next().unwrap().trim();
+    for path in paths.flatten() {
+        let file_name = path.path();
+        let error = |reason: String| MapError{ file: file_name.display().to_string(), reason };
+        // "123 - Name.txt" or "123-Name.txt"
+        let stem = file_name.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let Ok(id) = stem.split('-').next().unwrap_or("").trim().parse::<u64>() else {
+            errors.push(error("file name doesn't start with a province id".to_string()));
+            continue;
+        };
+        let data = match fs::read(&file_name) {
+            Ok(data) => data,
+            Err(e) => {
+                errors.push(error(e.to_string()));
+                continue;
+            }
+        };
+        match parse_province_history(data.as_slice()) {
+            Ok(history) => {
+                if histories.insert(id, history).is_some() {
+                    errors.push(error(format!("province {} has more than one history file", id)));
                 }
-                let id = name.parse::<u64>().unwrap();
-                let file = fs::read(file.path()).expect("error reading file");
-                let history = jomini::TextDeserializer::from_windows1252_slice(file.as_slice());
-                let history: ProvinceHistory = history.unwrap().deserialize().unwrap();
-                histories.insert(id, history);
             }
-            _ => {}
+            Err(reason) => errors.push(error(reason)),
         }
-        // break;
     }
-    histories
+    (histories, errors)
 }
 
 #[cfg(test)]
@@ -272,6 +720,91 @@ mod tests {
         let map = parse_map();
     }
 
+    fn assemble_fixture(area: &[u8], region: &[u8], superregion: &[u8]) -> (Vec<SuperRegion>, MapReport) {
+        let localisations = HashMap::from([("PROV1".to_string(), "Lorentainé".to_string())]);
+        let histories = BTreeMap::from([(1, ProvinceHistory::default()), (2, ProvinceHistory::default())]);
+        let geography = BTreeMap::from([(4, Geography{ sea: true, ..Default::default() })]);
+        let sources = MapSources{ localisations: &localisations, histories: &histories, geography: &geography };
+        let mut report = MapReport::default();
+        let map = assemble_map(&sources, area, region, superregion, &mut report);
+        (map, report)
+    }
+
+    #[test]
+    fn test_assemble_map() {
+        let (map, report) = assemble_fixture(
+            b"lorentaine_area = { 1 2 } sea_area = { 4 }",
+            b"lencenor_region = { areas = { lorentaine_area } } sea_region = { areas = { sea_area } }",
+            b"lencenor_superregion = { lencenor_region sea_region restrict_charter }");
+        assert!(report.is_empty(), "{:?}", report);
+        assert_eq!(map.len(), 1);
+        assert!(map[0].restrict_charter);
+        let region = map[0].regions.get(&"lencenor_region".to_string()).unwrap();
+        let area = region.areas.get(&"lorentaine_area".to_string()).unwrap();
+        assert_eq!(area.provinces.get(&1).unwrap().name, "Lorentainé");
+    }
+
+    #[test]
+    fn test_assemble_map_report() {
+        let (_map, report) = assemble_fixture(
+            b"lorentaine_area = { 1 x2 3 } deranne_area = { 2 1 } lost_area = { 4 } old_area = { }",
+            b"lencenor_region = { areas = { lorentaine_area } } stray_region = { areas = { deranne_area } }
+                gone_region = { areas = { missing_area } }",
+            b"lencenor_superregion = { lencenor_region gone_region unknown_region }");
+        assert_eq!(report.errors.iter().map(|e| e.reason.as_str()).collect::<Vec<_>>(), vec![
+            "invalid province id in lorentaine_area",
+            "province 1 is in both lorentaine_area and deranne_area",
+            "unknown or repeated area missing_area in gone_region",
+            "unknown or repeated region unknown_region in lencenor_superregion",
+        ]);
+        assert_eq!(report.missing_history, vec![3]);
+        assert_eq!(report.areas_without_region, vec!["lost_area"]);
+        assert_eq!(report.regions_without_super_region, vec!["stray_region"]);
+        assert_eq!(report.empty_regions, vec!["gone_region"]);
+    }
+
+    #[test]
+    fn test_assemble_map_bad_file() {
+        let (map, report) = assemble_fixture(b"lorentaine_area = { 1 }", b"lencenor_region = { areas = {", b"");
+        assert!(map.is_empty());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].file, "map/region.txt");
+    }
+
+    #[test]
+    fn test_province_history_state_at() {
+        let history = parse_province_history(b"owner = A01 culture = lorentish base_tax = 3
+            1444.11.11 = { owner = A02 }
+            1422.1.1 = { culture = high_lorentish }
+            1500.1.1 = { owner = A03 religion = regent_court }").unwrap();
+        assert_eq!(history.entries[0].date, Date::from_ymd(1422, 1, 1));
+        let state = history.state_at(Date::from_ymd(1444, 11, 11));
+        assert_eq!(state.owner.as_deref(), Some("A02"));
+        assert_eq!(state.culture.as_deref(), Some("high_lorentish"));
+        assert_eq!(state.religion, None);
+        assert_eq!(state.base_tax, Some(3));
+        assert_eq!(history.state_at(Date::from_ymd(1400, 1, 1)).owner.as_deref(), Some("A01"));
+        assert_eq!(history.timeline().len(), 3);
+    }
+
+    #[test]
+    fn test_province_history_errors() {
+        assert!(parse_province_history(b"owner = A01 1444.11.11 = { owner = \"A02 }").is_err());
+    }
+
+    #[test]
+    fn test_parse_terrain_and_climate() {
+        let terrain = parse_terrain(b"categories = { forest = { color = { 31 82 36 } terrain_override = { 12 13 } } }
+            terrain = { forest_1 = { type = forest color = { 3 4 } } }").unwrap();
+        assert_eq!(terrain.indices.get(&4).map(|s| s.as_str()), Some("forest"));
+        assert_eq!(terrain.overrides.get(&13).map(|s| s.as_str()), Some("forest"));
+
+        let climate = parse_climate(b"tropical = { 1 2 } severe_winter = { 2 } impassable = { 3 } equator_y_on_province_image = 656").unwrap();
+        assert_eq!(climate.get(&2).unwrap().climate.as_deref(), Some("tropical"));
+        assert_eq!(climate.get(&2).unwrap().winter.as_deref(), Some("severe_winter"));
+        assert!(climate.get(&3).unwrap().impassable);
+    }
+
     #[test]
     fn test_parse_continents() {
         parse_continents();
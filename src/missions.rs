@@ -4,6 +4,9 @@ use std::fs;
 use jomini::text::ValueReader;
 use jomini::{TextTape, Windows1252Encoding};
 
+use crate::snapshot::Snapshot;
+use crate::triggers::parse_condition;
+
 #[derive(Debug, Default)]
 pub struct MissionTree {
     pub generic: bool,
@@ -383,26 +386,12 @@ fn parse_trigger(trigger: &mut Trigger, value: &ValueReader<Windows1252Encoding>
     }
 }
 
-pub fn tags_with_missions() -> HashSet<String> {
+// A country has missions when a tree's potential holds for it at the start, or can't be decided
+// but names the country's tag, e.g. tag = A01 together with a flag check
+pub fn tags_with_missions(snapshot: &Snapshot) -> HashSet<String> {
     let mut tags = HashSet::new();
     let paths = fs::read_dir("./anbennar/missions").expect("Missing missions directory");
 
-    fn recursively_find_tags(mut tags: HashSet<String>, obj: ValueReader<Windows1252Encoding>) -> HashSet<String> {
-        if let Ok(inner) = obj.read_object() {
-            for (key, _op, value) in inner.fields() {
-                if key.read_str() == "NOT" {
-                    continue;
-                }
-                if key.read_str() == "tag" || key.read_str() == "was_tag" {
-                    tags.insert(value.read_string().expect("could not parse as string"));
-                } else {
-                    tags = recursively_find_tags(tags.clone(), value);
-                }
-            }
-        }
-        tags
-    }
-
     for path in paths {
         match path {
             Ok(file) => {
@@ -412,9 +401,18 @@ pub fn tags_with_missions() -> HashSet<String> {
                 for (_key, _op, value) in reader.fields() {
                     if let Ok(mission_tree) = value.read_object() {
                         for (key, _op, value) in mission_tree.fields() {
-                            let key = key.read_str();
-                            if key == "potential" {
-                                tags.extend(recursively_find_tags(HashSet::new(), value));
+                            if key.read_str() != "potential" {
+                                continue;
+                            }
+                            let potential = parse_condition(&value);
+                            let named = potential.tags();
+                            let candidates = snapshot.countries.keys().chain(named.iter()).collect::<HashSet<_>>();
+                            for tag in candidates {
+                                match snapshot.evaluate(tag, &potential) {
+                                    Some(true) => { tags.insert(tag.clone()); }
+                                    None if named.contains(tag) => { tags.insert(tag.clone()); }
+                                    _ => {}
+                                }
                             }
                         }
                     }
@@ -485,7 +483,7 @@ mod tests {
 
     #[test]
     pub fn test_tags_with_missions() {
-        let actual = tags_with_missions();
+        let actual = tags_with_missions(&Snapshot::at_start());
         assert!(actual.contains(&"Z43".to_string()));
         assert!(actual.contains(&"U08".to_string()));
     }
@@ -1,12 +1,18 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::string::String;
 use jomini::text::{ScalarReader, ValueReader};
-use jomini::Windows1252Encoding;
+use jomini::{Scalar, Windows1252Encoding};
 use phf::phf_map;
 use convert_case::{Case, Casing};
 
+use crate::bundled_modifiers::parse_bundled_modifiers;
+use crate::governments::parse_government_reforms;
+use crate::greatprojects::{parse_all_great_projects, Tier};
+use crate::ideas::{parse_idea_groups, parse_ideas, parse_policies};
+use crate::religions::parse_religious_groups;
 use crate::modifiers::ModifierFormat::{Flat, Percent};
 use crate::modifiers::ModifierNormal::{Negative, Positive};
+use crate::modifiers::ModifierSuffix::{Ducats, Months, Years};
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub enum ModifierFormat {
@@ -32,34 +38,144 @@ pub struct Modifier {
     pub multiplier: usize,
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ModifierSuffix {
+    #[default]
+    None,
+    Years,
+    Months,
+    Ducats,
+}
+
+impl ModifierSuffix {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModifierSuffix::None => "",
+            ModifierSuffix::Years => " years",
+            ModifierSuffix::Months => " months",
+            ModifierSuffix::Ducats => " ducats",
+        }
+    }
+}
+
+// Display overrides for modifiers that don't render with the defaults of their format
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModifierDisplay {
+    pub precision: Option<usize>,
+    pub suffix: ModifierSuffix,
+    pub inverted: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModifierSourceType {
+    Idea,
+    GovernmentReform,
+    Religion,
+    ReligiousSchool,
+    Icon,
+    GreatProject,
+    BundledModifier,
+    Policy,
+}
+
+impl ModifierSourceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModifierSourceType::Idea => "Idea",
+            ModifierSourceType::GovernmentReform => "Government reform",
+            ModifierSourceType::Religion => "Religion",
+            ModifierSourceType::ReligiousSchool => "Religious school",
+            ModifierSourceType::Icon => "Icon",
+            ModifierSourceType::GreatProject => "Great project",
+            ModifierSourceType::BundledModifier => "Bundled modifier",
+            ModifierSourceType::Policy => "Policy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModifierUse {
+    pub source_type: ModifierSourceType,
+    pub source: String,
+    pub value: Vec<u8>, // lifetime problems on scalar
+}
+
 #[derive(Debug, Default)]
 pub struct ModifierUses {
     pub modifier: Modifier,
-    pub uses: BTreeMap<String, u8>,
+    pub uses: Vec<ModifierUse>,
+}
+
+impl ModifierUses {
+    pub fn display_name(&self) -> String {
+        // Names with scripted localisation like [Country.GetBurghersName] can't be page titles
+        if self.modifier.name.is_empty() || self.modifier.name.contains('[') {
+            return self.modifier.id.to_case(Case::Title);
+        }
+        self.modifier.name.to_string()
+    }
+
+    pub fn page_title(&self) -> String {
+        format!("{} sources", self.display_name())
+    }
+
+    pub fn count(&self, source_type: ModifierSourceType) -> usize {
+        self.uses.iter().filter(|u| u.source_type == source_type).count()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FormattedModifier {
+    pub id: String,
+    pub value: String,
+    pub colour: &'static str,
+    pub description: String,
+}
+
+impl FormattedModifier {
+    pub fn to_template(&self, subst: bool) -> String {
+        format!(
+            "{{{{{subst}Modifier |type={colour}|value={value}|description={desc} }}}}",
+            subst = if subst { "subst:" } else { "" },
+            colour = self.colour,
+            value = self.value,
+            desc = self.description
+        )
+    }
 }
 
 impl Modifier {
+    pub fn display(&self) -> ModifierDisplay {
+        MODIFIER_DISPLAY.get(self.id).copied().unwrap_or_default()
+    }
+
     pub fn to_human_readable(self, amount: f32) -> String {
-        let inverted = vec![
-            "reduced_liberty_desire",
-            "reduced_liberty_desire_on_same_continent"
-        ];
+        let display = self.display();
         let mut sign = "+";
         // if (amount < 0.0) || (amount > 0.0 && self.normal == Negative) {
-        if (inverted.contains(&self.id) && amount > 0.0) || (amount < 0.0 && !inverted.contains(&self.id)) {
+        if (display.inverted && amount > 0.0) || (amount < 0.0 && !display.inverted) {
             sign = "-";
         }
+        let amount = (amount * self.multiplier as f32).abs();
+        let amount = match (self.format, display.precision) {
+            (ModifierFormat::None, _) => return "".to_string(),
+            (_, Some(precision)) => format!("{amount:.precision$}"),
+            (Percent, None) => amount.round().to_string(),
+            (Flat, None) => amount.to_string(),
+        };
+        let suffix = display.suffix.as_str();
         match self.format {
-            Percent => {
-                format!("{sign}{amount}%", amount = (amount * self.multiplier as f32).abs().round())
-            }
-            Flat => {
-                format!("{sign}{amount}", amount = (amount * self.multiplier as f32).abs())
-            }
-            ModifierFormat::None => {
-                "".to_string()
-            }
+            Percent => format!("{sign}{amount}%{suffix}"),
+            _ => format!("{sign}{amount}{suffix}"),
+        }
+    }
+
+    // "bonus" or "malus" for the Modifier template
+    pub fn colour(self, amount: f64) -> &'static str {
+        if amount.is_sign_positive() && self.normal == Negative || amount.is_sign_negative() && self.normal == Positive {
+            return "malus";
         }
+        "bonus"
     }
 }
 
@@ -75,19 +191,173 @@ pub fn get_modifier(id: &String) -> Option<Modifier> {
     }
 }
 
-pub fn localise_strings(description: &String, value: &String) -> (String, String) {
-    let mut desc: String = description.clone();
-    let mut val: String = value.clone();
-    match MODIFIERS.get(description.to_lowercase().as_str()) {
-        Some(modifier) => {
-            desc = modifier.name.to_string();
-            if let Ok(parsed) = value.parse::<f32>() {
-                val = modifier.to_human_readable(parsed);
+pub fn format_modifier(key: &str, value: &[u8]) -> Option<FormattedModifier> {
+    let modifier = MODIFIERS.get(key.to_lowercase().as_str())?;
+    let mut formatted = FormattedModifier {
+        id: modifier.id.to_string(),
+        value: String::from_utf8_lossy(value).to_string(),
+        colour: "bonus",
+        description: modifier.name.to_string(),
+    };
+    match Scalar::new(value).to_f64() {
+        Ok(amount) => {
+            formatted.value = modifier.to_human_readable(amount as f32);
+            formatted.colour = modifier.colour(amount);
+        }
+        Err(_) => {
+            // yes/no switches like allow_client_states
+            if let Ok(enabled) = Scalar::new(value).to_bool() {
+                formatted.value = "".to_string();
+                if !enabled {
+                    formatted.colour = "malus";
+                }
+            }
+        }
+    }
+    Some(formatted)
+}
+
+pub fn format_modifiers(modifiers: &BTreeMap<String, Vec<u8>>) -> Vec<FormattedModifier> {
+    modifiers
+        .iter()
+        .filter_map(|(key, value)| format_modifier(key, value.as_slice()))
+        .collect()
+}
+
+// Keyed by modifier id, every modifier in MODIFIERS gets an entry even without uses
+#[derive(Debug, Default)]
+pub struct ModifierIndex {
+    pub modifiers: BTreeMap<&'static str, ModifierUses>,
+}
+
+impl ModifierIndex {
+    pub fn new() -> ModifierIndex {
+        let mut index = ModifierIndex::default();
+        for (id, modifier) in MODIFIERS.entries() {
+            index.modifiers.insert(id, ModifierUses{ modifier: *modifier, uses: vec![] });
+        }
+        index
+    }
+
+    pub fn record(&mut self, key: &str, value: &[u8], source_type: ModifierSourceType, source: &String) {
+        if let Some(uses) = self.modifiers.get_mut(key.to_lowercase().as_str()) {
+            uses.uses.push(ModifierUse{
+                source_type,
+                source: source.to_string(),
+                value: value.to_vec(),
+            });
+        }
+    }
+
+    pub fn record_all(&mut self, modifiers: &BTreeMap<String, Vec<u8>>, source_type: ModifierSourceType, source: &String) {
+        for (key, value) in modifiers {
+            self.record(key, value.as_slice(), source_type, source);
+        }
+    }
+
+    pub fn used(&self) -> impl Iterator<Item = &ModifierUses> {
+        self.modifiers.values().filter(|u| !u.uses.is_empty())
+    }
+}
+
+pub fn parse_modifier_uses(localisations: &HashMap<String, String>) -> ModifierIndex {
+    let mut index = ModifierIndex::new();
+    let localise = |key: &String| -> String {
+        match localisations.get(key) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => key.to_string(),
+        }
+    };
+
+    for set in parse_ideas().idea_sets.iter() {
+        let set_name = localise(&set.name);
+        for (key, value) in set.start.iter() {
+            index.record(key, value.as_bytes(), ModifierSourceType::Idea, &format!("{set_name} (traditions)"));
+        }
+        for idea in set.ideas.iter() {
+            let source = format!("{set_name}: {}", localise(&idea.name));
+            for (key, value) in idea.effects.iter() {
+                index.record(key, value.as_bytes(), ModifierSourceType::Idea, &source);
+            }
+        }
+        for (key, value) in set.bonus.iter() {
+            index.record(key, value.as_bytes(), ModifierSourceType::Idea, &format!("{set_name} (ambition)"));
+        }
+    }
+
+    for group in parse_idea_groups().iter() {
+        let group_name = localise(&group.name);
+        for idea in group.ideas.iter() {
+            let source = format!("{group_name}: {}", localise(&idea.name));
+            for (key, value) in idea.effects.iter() {
+                index.record(key, value.as_bytes(), ModifierSourceType::Idea, &source);
+            }
+        }
+        for (key, value) in group.bonus.iter() {
+            index.record(key, value.as_bytes(), ModifierSourceType::Idea, &format!("{group_name} (ambition)"));
+        }
+    }
+
+    for policy in parse_policies().iter() {
+        let name = localise(&policy.idea.name);
+        for (key, value) in policy.idea.effects.iter() {
+            index.record(key, value.as_bytes(), ModifierSourceType::Policy, &name);
+        }
+    }
+
+    for reform in parse_government_reforms(Some(localisations)) {
+        let name = reform.name.clone().unwrap_or(reform.id.clone());
+        index.record_all(&reform.modifiers, ModifierSourceType::GovernmentReform, &name);
+    }
+
+    for group in parse_religious_groups(Some(localisations)) {
+        for religion in group.religions.values() {
+            let name = localise(&religion.id);
+            index.record_all(&religion.country_modifiers, ModifierSourceType::Religion, &name);
+            index.record_all(&religion.province_modifiers, ModifierSourceType::Religion, &format!("{name} (provinces)"));
+            index.record_all(&religion.country_as_secondary_modifiers, ModifierSourceType::Religion, &format!("{name} (secondary)"));
+            for (id, icon) in religion.orthodox_icons.iter() {
+                index.record_all(&icon.country_modifiers, ModifierSourceType::Icon, &localise(id));
+            }
+        }
+        for (id, school) in group.schools.iter().flatten() {
+            index.record_all(&school.country_modifiers, ModifierSourceType::ReligiousSchool, &localise(id));
+        }
+    }
+
+    for project in parse_all_great_projects() {
+        let name = localise(&project.id);
+        let tiers = [&project.tier_1, &project.tier_2, &project.tier_3];
+        for (level, tier) in tiers.iter().enumerate() {
+            if let Some(tier) = tier {
+                record_tier(&mut index, tier, &format!("{name} (tier {})", level + 1));
             }
-            (desc.clone(), val.clone())
+        }
+    }
+
+    for bundled_modifier in parse_bundled_modifiers(Some(localisations)) {
+        let name = bundled_modifier.name.clone().unwrap_or(bundled_modifier.id.to_case(Case::Title));
+        index.record_all(&bundled_modifier.modifiers, ModifierSourceType::BundledModifier, &name);
+    }
+
+    index
+}
+
+fn record_tier(index: &mut ModifierIndex, tier: &Tier, source: &String) {
+    index.record_all(&tier.province_modifiers, ModifierSourceType::GreatProject, source);
+    index.record_all(&tier.area_modifier, ModifierSourceType::GreatProject, &format!("{source}, area"));
+    index.record_all(&tier.region_modifier, ModifierSourceType::GreatProject, &format!("{source}, region"));
+    index.record_all(&tier.country_modifiers, ModifierSourceType::GreatProject, &format!("{source}, country"));
+}
+
+// Unknown keys are passed through unchanged, check with get_modifier() to report them
+pub fn localise_strings(description: &String, value: &String) -> (String, String) {
+    match format_modifier(description, value.as_bytes()) {
+        Some(formatted) => {
+            (formatted.description, formatted.value)
         },
         None => {
-            panic!("No mapping for {}", desc);
+            (description.clone(), value.clone())
         }
     }
 }
@@ -153,10 +423,77 @@ mod tests {
         assert_eq!(localised, ("Discipline".to_string(), "+5%".to_string()));
         let localised = localise_strings(&"advisor_pool".to_string(), &"-1".to_string());
         assert_eq!(localised, ("Possible Advisors".to_string(), "-1".to_string()));
+        let localised = localise_strings(&"not_a_modifier".to_string(), &"1".to_string());
+        assert_eq!(localised, ("not_a_modifier".to_string(), "1".to_string()));
+    }
+
+    #[test]
+    pub fn test_display_overrides() {
+        let modifier = get_modifier(&"years_of_nationalism".to_string());
+        assert_eq!(modifier.unwrap().to_human_readable(-5f32), "-5 years");
+
+        let modifier = get_modifier(&"prestige".to_string());
+        assert_eq!(modifier.unwrap().to_human_readable(0.5), "+0.50");
+
+        let modifier = get_modifier(&"global_tax_income".to_string());
+        assert_eq!(modifier.unwrap().to_human_readable(12f32), "+12 ducats");
+    }
+
+    #[test]
+    pub fn test_modifier_index() {
+        let mut index = ModifierIndex::new();
+        assert_eq!(index.modifiers.len(), MODIFIERS.len());
+        index.record("Discipline", b"0.05", ModifierSourceType::Idea, &"Lorentish Ideas: Knights".to_string());
+        index.record("not_a_modifier", b"0.05", ModifierSourceType::Idea, &"Lorentish Ideas: Knights".to_string());
+        let uses = index.modifiers.get("discipline").unwrap();
+        assert_eq!(uses.uses.len(), 1);
+        assert_eq!(uses.count(ModifierSourceType::Idea), 1);
+        assert_eq!(uses.page_title(), "Discipline sources");
+        assert_eq!(index.used().count(), 1);
+    }
+
+    #[test]
+    pub fn test_format_modifier() {
+        let formatted = format_modifier("adm_tech_cost_modifier", b"-0.1").unwrap();
+        assert_eq!(formatted.value, "-10%");
+        assert_eq!(formatted.colour, "bonus");
+        assert_eq!(formatted.description, "Administrative Technology Cost");
+
+        let formatted = format_modifier("discipline", b"-0.05").unwrap();
+        assert_eq!(formatted.colour, "malus");
+        assert_eq!(formatted.to_template(false), "{{Modifier |type=malus|value=-5%|description=Discipline }}");
+
+        let formatted = format_modifier("allow_client_states", b"yes").unwrap();
+        assert_eq!(formatted.value, "");
+        assert_eq!(formatted.colour, "bonus");
+
+        let formatted = format_modifier("parliament_debate_duration", b"6").unwrap();
+        assert_eq!(formatted.value, "+6 months");
+
+        assert!(format_modifier("disciplined", b"0.05").is_none());
     }
 }
 
 
+pub const MODIFIER_DISPLAY: phf::Map<&'static str, ModifierDisplay> = phf_map!{
+    "army_tradition" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "devotion" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "global_tax_income" => ModifierDisplay{ precision: None, suffix: Ducats, inverted: false },
+    "horde_unity" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "inflation_reduction" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "legitimacy" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "local_years_of_nationalism" => ModifierDisplay{ precision: None, suffix: Years, inverted: false },
+    "national_focus_years" => ModifierDisplay{ precision: None, suffix: Years, inverted: false },
+    "navy_tradition" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "parliament_debate_duration" => ModifierDisplay{ precision: None, suffix: Months, inverted: false },
+    "parliament_effect_duration" => ModifierDisplay{ precision: None, suffix: Months, inverted: false },
+    "prestige" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "reduced_liberty_desire" => ModifierDisplay{ precision: None, suffix: ModifierSuffix::None, inverted: true },
+    "reduced_liberty_desire_on_same_continent" => ModifierDisplay{ precision: None, suffix: ModifierSuffix::None, inverted: true },
+    "republican_tradition" => ModifierDisplay{ precision: Some(2), suffix: ModifierSuffix::None, inverted: false },
+    "years_of_nationalism" => ModifierDisplay{ precision: None, suffix: Years, inverted: false },
+};
+
 pub const MODIFIERS: phf::Map<&'static str, Modifier> = phf_map!{
     "accept_vassalization_reasons" => Modifier{ id: "accept_vassalization_reasons", name: "Vassalization Acceptance", format: Flat, normal: Positive, multiplier: 1 },
     "acolytes_influence_modifier" => Modifier{ id: "acolytes_influence_modifier", name: "Acolyte Influence", format: Percent, normal: Positive, multiplier: 100 },
@@ -373,7 +710,7 @@ pub const MODIFIERS: phf::Map<&'static str, Modifier> = phf_map!{
     "global_spy_defence" => Modifier{ id: "global_spy_defence", name: "Foreign Spy Detection", format: Percent, normal: Positive, multiplier: 100 },
     "global_supply_limit_modifier" => Modifier{ id: "global_supply_limit_modifier", name: "National Supply Limit Modifier", format: Percent, normal: Positive, multiplier: 100 },
     "global_tariffs" => Modifier{ id: "global_tariffs", name: "Merchants", format: Percent, normal: Positive, multiplier: 100 },
-    "global_tax_income" => Modifier{ id: "global_tax_income", name: "Global Tax Income", format: Percent, normal: Positive, multiplier: 1 },
+    "global_tax_income" => Modifier{ id: "global_tax_income", name: "Global Tax Income", format: Flat, normal: Positive, multiplier: 1 },
     "global_tax_modifier" => Modifier{ id: "global_tax_modifier", name: "National Tax Modifier", format: Percent, normal: Positive, multiplier: 100 },
     "global_trade_goods_size_modifier" => Modifier{ id: "global_trade_goods_size_modifier", name: "Goods Produced Modifier", format: Percent, normal: Positive, multiplier: 100 },
     "global_trade_power" => Modifier{ id: "global_trade_power", name: "Global Trade Power", format: Percent, normal: Positive, multiplier: 100 },
@@ -511,7 +848,7 @@ pub const MODIFIERS: phf::Map<&'static str, Modifier> = phf_map!{
     "local_tolerance_of_heathens" => Modifier{ id: "local_tolerance_of_heathens", name: "Local Tolerance Of Heathens", format: Percent, normal: Positive, multiplier: 1 },
     "local_unrest" => Modifier{ id: "local_unrest", name: "Local Unrest", format: Percent, normal: Positive, multiplier: 1 },
     "local_warscore_cost_modifier" => Modifier{ id: "local_warscore_cost_modifier", name: "Local Warscore Cost Modifier", format: Percent, normal: Positive, multiplier: 100 },
-    "local_years_of_nationalism" => Modifier{ id: "local_years_of_nationalism", name: "Local Years Of Nationalism", format: Percent, normal: Positive, multiplier: 1 },
+    "local_years_of_nationalism" => Modifier{ id: "local_years_of_nationalism", name: "Local Years Of Nationalism", format: Flat, normal: Positive, multiplier: 1 },
     "loot_amount" => Modifier{ id: "loot_amount", name: "Looting Speed", format: Percent, normal: Positive, multiplier: 100 },
     "lowercastes_loyalty_modifier" => Modifier{ id: "lowercastes_loyalty_modifier", name: "[Country.GetLower CastesName] Loyalty Equilibrium", format: Percent, normal: Positive, multiplier: 100 },
     "mages_influence_modifier" => Modifier{ id: "mages_influence_modifier", name: "[Country.GetMagesName] Influence", format: Percent, normal: Positive, multiplier: 100 },
@@ -633,8 +970,8 @@ pub const MODIFIERS: phf::Map<&'static str, Modifier> = phf_map!{
     "papal_influence" => Modifier{ id: "papal_influence", name: "Papal Influence", format: Flat, normal: Positive, multiplier: 1 },
     "papal_influence_from_cardinals" => Modifier{ id: "papal_influence_from_cardinals", name: "Rectorate Influence from Veridicals", format: Percent, normal: Positive, multiplier: 100 },
     "parliament_backing_chance" => Modifier{ id: "parliament_backing_chance", name: "Parliament Backing Chance", format: Percent, normal: Positive, multiplier: 100 },
-    "parliament_debate_duration" => Modifier{ id: "parliament_debate_duration", name: "Parliament Debate Duration", format: Percent, normal: Positive, multiplier: 1 },
-    "parliament_effect_duration" => Modifier{ id: "parliament_effect_duration", name: "Parliament Effect Duration", format: Percent, normal: Positive, multiplier: 1 },
+    "parliament_debate_duration" => Modifier{ id: "parliament_debate_duration", name: "Parliament Debate Duration", format: Flat, normal: Positive, multiplier: 1 },
+    "parliament_effect_duration" => Modifier{ id: "parliament_effect_duration", name: "Parliament Effect Duration", format: Flat, normal: Positive, multiplier: 1 },
     "pen_cloak_and_swagger_influence" => Modifier{ id: "pen_cloak_and_swagger_influence", name: "Cloak and Swagger Influence", format: Percent, normal: Positive, multiplier: 100 },
     "pen_clodhoppers_influence" => Modifier{ id: "pen_clodhoppers_influence", name: "Clodhopper Influence", format: Percent, normal: Positive, multiplier: 100 },
     "pen_discombobulatorium_influence" => Modifier{ id: "pen_discombobulatorium_influence", name: "Discombobulatorium Influence", format: Percent, normal: Positive, multiplier: 100 },
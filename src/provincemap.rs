@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use jomini::text::ValueReader;
+use jomini::Windows1252Encoding;
+
+pub type Colour = [u8; 3];
+
+// Wasteland, lakes, seas and anything the renderer has no colour for
+pub const UNKNOWN: Colour = [94, 94, 94];
+pub const BORDER: Colour = [30, 30, 30];
+pub const HIGHLIGHT: Colour = [230, 40, 40];
+
+// Reads `color = { r g b }`, either 0-255 integers or 0-1 floats like the trade goods use
+pub fn read_colour(value: &ValueReader<Windows1252Encoding>) -> Option<Colour> {
+    let array = value.read_array().ok()?;
+    let parts = array.values()
+        .filter_map(|v| v.read_scalar().ok())
+        .filter_map(|v| v.to_f64().ok())
+        .collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return None;
+    }
+    let scale = if parts.iter().all(|p| *p <= 1.0) { 255.0 } else { 1.0 };
+    Some([
+        (parts[0] * scale).round() as u8,
+        (parts[1] * scale).round() as u8,
+        (parts[2] * scale).round() as u8,
+    ])
+}
+
+// A stable colour for things the game doesn't colour itself, e.g. cultures
+pub fn hashed_colour(key: &str) -> Colour {
+    // FNV-1a
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in key.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    [(hash >> 16) as u8 | 0x30, (hash >> 8) as u8 | 0x30, hash as u8 | 0x30]
+}
+
+// Green to red, development above `max` is clamped
+pub fn gradient_colour(value: u64, max: u64) -> Colour {
+    let t = value.min(max) as f64 / max.max(1) as f64;
+    [(255.0 * t) as u8, (255.0 * (1.0 - t)) as u8, 40]
+}
+
+// definition.csv: province;red;green;blue;name;x
+pub fn parse_definitions(data: &[u8]) -> HashMap<Colour, u64> {
+    let mut definitions = HashMap::new();
+    let data = String::from_utf8_lossy(data);
+    for line in data.lines() {
+        let parts = line.split(';').collect::<Vec<_>>();
+        if parts.len() < 4 {
+            continue;
+        }
+        // the header and the odd commented line don't parse
+        let id = parts[0].trim().parse::<u64>();
+        let r = parts[1].trim().parse::<u8>();
+        let g = parts[2].trim().parse::<u8>();
+        let b = parts[3].trim().parse::<u8>();
+        if let (Ok(id), Ok(r), Ok(g), Ok(b)) = (id, r, g, b) {
+            definitions.insert([r, g, b], id);
+        }
+    }
+    definitions
+}
+
+#[derive(Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // RGB, top to bottom
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image { width, height, data: vec![0; width * height * 3] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Colour {
+        let i = (y * self.width + x) * 3;
+        [self.data[i], self.data[i + 1], self.data[i + 2]]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, colour: Colour) {
+        let i = (y * self.width + x) * 3;
+        self.data[i..i + 3].copy_from_slice(&colour);
+    }
+
+    // Reads an uncompressed 24 bit BMP, which is what provinces.bmp is
+    pub fn from_bmp(data: &[u8]) -> Result<Image, String> {
+        let header = BmpHeader::read(data, 24)?;
+        let mut image = Image::new(header.width, header.height);
+        for y in 0..header.height {
+            let start = header.row_start(y);
+            for x in 0..header.width {
+                let p = start + x * 3;
+                // BGR
+                image.set(x, y, [data[p + 2], data[p + 1], data[p]]);
+            }
+        }
+        Ok(image)
+    }
+
+    // Nearest neighbour, so the flat colours stay flat and compress well
+    pub fn downscale(&self, factor: usize) -> Image {
+        let factor = factor.max(1);
+        let mut image = Image::new(self.width / factor, self.height / factor);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                image.set(x, y, self.get(x * factor, y * factor));
+            }
+        }
+        image
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        // every row gets the Sub filter so runs of one colour turn into runs of zeroes
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for y in 0..self.height {
+            raw.push(1);
+            let row = &self.data[y * self.width * 3..(y + 1) * self.width * 3];
+            for (i, byte) in row.iter().enumerate() {
+                raw.push(if i < 3 { *byte } else { byte.wrapping_sub(row[i - 3]) });
+            }
+        }
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bit RGB, deflate, adaptive filtering, not interlaced
+        header.extend([8, 2, 0, 0, 0]);
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_png()).map_err(|e| format!("error writing {}: {}", path.display(), e))
+    }
+}
+
+struct BmpHeader {
+    offset: usize,
+    width: usize,
+    height: usize,
+    stride: usize,
+    bottom_up: bool,
+}
+
+impl BmpHeader {
+    fn read(data: &[u8], bpp: u16) -> Result<BmpHeader, String> {
+        if data.len() < 54 || &data[0..2] != b"BM" {
+            return Err("not a BMP file".to_string());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        if u16_at(28) != bpp || u32_at(30) != 0 {
+            return Err(format!("unsupported BMP: {} bpp, compression {}", u16_at(28), u32_at(30)));
+        }
+        let width = u32_at(18) as i32;
+        let height = u32_at(22) as i32;
+        let (width, height) = (width.unsigned_abs() as usize, height.unsigned_abs() as usize);
+        let header = BmpHeader {
+            offset: u32_at(10) as usize,
+            width,
+            height,
+            stride: (width * bpp as usize / 8 + 3) & !3,
+            // rows are stored bottom up unless the height is negative
+            bottom_up: (u32_at(22) as i32) > 0,
+        };
+        if data.len() < header.offset + header.stride * header.height {
+            return Err("BMP pixel data is truncated".to_string());
+        }
+        Ok(header)
+    }
+
+    fn row_start(&self, y: usize) -> usize {
+        let row = if self.bottom_up { self.height - 1 - y } else { y };
+        self.offset + row * self.stride
+    }
+}
+
+// Palette indices of an 8 bit BMP like terrain.bmp, top to bottom
+pub fn read_indexed_bmp(data: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    let header = BmpHeader::read(data, 8)?;
+    let mut indices = Vec::with_capacity(header.width * header.height);
+    for y in 0..header.height {
+        let start = header.row_start(y);
+        indices.extend_from_slice(&data[start..start + header.width]);
+    }
+    Ok((header.width, header.height, indices))
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    // least significant bit first, like deflate wants for everything but the Huffman codes
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let reversed = code.reverse_bits() >> (32 - bits);
+        self.write(reversed, bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+const LENGTH_BASE: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+// Fixed Huffman codes from RFC 1951 3.2.6
+fn write_symbol(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+// A single fixed Huffman block that only knows about runs of the previous byte, which is
+// all a filtered flat colour map needs
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: vec![], buffer: 0, count: 0 };
+    // BFINAL, BTYPE = 01
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut i = 0;
+    while i < data.len() {
+        write_symbol(&mut writer, data[i] as u32);
+        let mut run = 0;
+        while i + 1 + run < data.len() && data[i + 1 + run] == data[i] && run < 258 {
+            run += 1;
+        }
+        if run >= 3 {
+            let code = LENGTH_BASE.iter().rposition(|b| *b as usize <= run).unwrap();
+            write_symbol(&mut writer, 257 + code as u32);
+            writer.write(run as u32 - LENGTH_BASE[code], LENGTH_EXTRA[code]);
+            // distance 1 is code 0, five bits, no extra bits
+            writer.write_code(0, 5);
+            i += 1 + run;
+        } else {
+            i += 1;
+        }
+    }
+    write_symbol(&mut writer, 256);
+    writer.finish()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend(((b << 16) | a).to_be_bytes());
+    out
+}
+
+#[derive(Debug)]
+pub struct ProvinceMap {
+    pub width: usize,
+    pub height: usize,
+    // index into ids per pixel
+    pub pixels: Vec<u32>,
+    // province ids in order of first appearance, ids[0] = 0 for colours not in definition.csv
+    pub ids: Vec<u64>,
+    // id -> (min x, min y, max x, max y)
+    pub bounds: BTreeMap<u64, (usize, usize, usize, usize)>,
+}
+
+impl ProvinceMap {
+    pub fn new(image: &Image, definitions: &HashMap<Colour, u64>) -> ProvinceMap {
+        let mut pixels = Vec::with_capacity(image.width * image.height);
+        let mut ids = vec![0];
+        let mut indices: HashMap<u64, u32> = HashMap::from([(0, 0)]);
+        let mut bounds: BTreeMap<u64, (usize, usize, usize, usize)> = BTreeMap::new();
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let id = definitions.get(&image.get(x, y)).copied().unwrap_or(0);
+                let index = *indices.entry(id).or_insert_with(|| {
+                    ids.push(id);
+                    (ids.len() - 1) as u32
+                });
+                pixels.push(index);
+                if id != 0 {
+                    let b = bounds.entry(id).or_insert((x, y, x, y));
+                    b.0 = b.0.min(x);
+                    b.1 = b.1.min(y);
+                    b.2 = b.2.max(x);
+                    b.3 = b.3.max(y);
+                }
+            }
+        }
+        ProvinceMap { width: image.width, height: image.height, pixels, ids, bounds }
+    }
+
+    fn index_at(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn id_at(&self, x: usize, y: usize) -> u64 {
+        self.ids[self.index_at(x, y) as usize]
+    }
+
+    // Colours every pixel by its province and draws the borders between provinces that got a colour
+    pub fn render<F: Fn(u64) -> Option<Colour>>(&self, colour: F) -> Image {
+        self.render_part(0, 0, self.width, self.height, colour)
+    }
+
+    // Same as render, but only the pixels in [x0, x1) x [y0, y1)
+    pub fn render_part<F: Fn(u64) -> Option<Colour>>(&self, x0: usize, y0: usize, x1: usize, y1: usize, colour: F) -> Image {
+        let (x1, y1) = (x1.min(self.width), y1.min(self.height));
+        let mut cache: Vec<Option<Option<Colour>>> = vec![None; self.ids.len()];
+        let mut image = Image::new(x1 - x0, y1 - y0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let index = self.index_at(x, y);
+                let c = *cache[index as usize].get_or_insert_with(|| colour(self.ids[index as usize]));
+                let border = c.is_some()
+                    && ((x + 1 < self.width && self.index_at(x + 1, y) != index)
+                        || (y + 1 < self.height && self.index_at(x, y + 1) != index));
+                image.set(x - x0, y - y0, if border { BORDER } else { c.unwrap_or(UNKNOWN) });
+            }
+        }
+        image
+    }
+
+    // The area around one province with the province itself highlighted
+    pub fn locator<F: Fn(u64) -> Option<Colour>>(&self, id: u64, colour: F, margin: usize) -> Option<Image> {
+        let (x0, y0, x1, y1) = *self.bounds.get(&id)?;
+        Some(self.render_part(
+            x0.saturating_sub(margin),
+            y0.saturating_sub(margin),
+            x1 + 1 + margin,
+            y1 + 1 + margin,
+            |p| if p == id { Some(HIGHLIGHT) } else { colour(p) },
+        ))
+    }
+}
+
+pub fn read_province_map() -> Result<ProvinceMap, String> {
+    let bmp = fs::read("./anbennar/map/provinces.bmp").map_err(|e| format!("error reading provinces.bmp: {}", e))?;
+    let definitions = fs::read("./anbennar/map/definition.csv").map_err(|e| format!("error reading definition.csv: {}", e))?;
+    let image = Image::from_bmp(bmp.as_slice())?;
+    Ok(ProvinceMap::new(&image, &parse_definitions(definitions.as_slice())))
+}
+
+pub fn parse_province_map() -> ProvinceMap {
+    read_province_map().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+    }
+
+    // Just enough of RFC 1951 to read back what deflate() writes: fixed Huffman codes and
+    // distances without extra bits
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut position = 0;
+        let mut bit = |count: u32| {
+            let mut value = 0;
+            for i in 0..count {
+                value |= ((data[position / 8] >> (position % 8)) as u32 & 1) << i;
+                position += 1;
+            }
+            value
+        };
+        assert_eq!((bit(1), bit(2)), (1, 1));
+        let mut out: Vec<u8> = vec![];
+        loop {
+            // Huffman codes are packed most significant bit first
+            let mut code = 0;
+            let mut length = 0;
+            let symbol = loop {
+                code = (code << 1) | bit(1);
+                length += 1;
+                match (length, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                    (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                    _ => assert!(length < 9, "invalid code"),
+                }
+            };
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let index = symbol as usize - 257;
+                    let run = LENGTH_BASE[index] + bit(LENGTH_EXTRA[index]);
+                    let distance = (0..5).fold(0, |d, _| (d << 1) | bit(1)) + 1;
+                    assert!(distance <= 4);
+                    for _ in 0..run {
+                        out.push(out[out.len() - distance as usize]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = Image::new(300, 2);
+        for x in 0..300 {
+            image.set(x, 0, [10, 20, 30]);
+            image.set(x, 1, if x < 150 { HIGHLIGHT } else { [x as u8, 0, 255] });
+        }
+        let png = image.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut chunks = BTreeMap::new();
+        let mut i = 8;
+        while i < png.len() {
+            let length = u32::from_be_bytes(png[i..i + 4].try_into().unwrap()) as usize;
+            let body = &png[i + 4..i + 8 + length];
+            let crc = u32::from_be_bytes(png[i + 8 + length..i + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            chunks.insert(String::from_utf8_lossy(&body[..4]).to_string(), body[4..].to_vec());
+            i += 12 + length;
+        }
+        let header = &chunks["IHDR"];
+        assert_eq!(u32::from_be_bytes(header[0..4].try_into().unwrap()), 300);
+        assert_eq!(u32::from_be_bytes(header[4..8].try_into().unwrap()), 2);
+        assert_eq!(&header[8..], &[8, 2, 0, 0, 0]);
+        assert!(chunks["IEND"].is_empty());
+
+        let idat = &chunks["IDAT"];
+        assert_eq!(&idat[..2], &[0x78, 0x01]);
+        let raw = inflate(&idat[2..idat.len() - 4]);
+        assert_eq!(raw.len(), (300 * 3 + 1) * 2);
+        let mut data = vec![];
+        for row in raw.chunks(300 * 3 + 1) {
+            assert_eq!(row[0], 1);
+            let start = data.len();
+            for (i, byte) in row[1..].iter().enumerate() {
+                data.push(if i < 3 { *byte } else { byte.wrapping_add(data[start + i - 3]) });
+            }
+        }
+        assert_eq!(data, image.data);
+    }
+
+    #[test]
+    fn test_province_map() {
+        // 2x2, bottom up, rows padded to 8 bytes
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0u8; 8]);
+        bmp.extend(54u32.to_le_bytes());
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(2u32.to_le_bytes());
+        bmp.extend(2u32.to_le_bytes());
+        bmp.extend(1u16.to_le_bytes());
+        bmp.extend(24u16.to_le_bytes());
+        bmp.extend([0u8; 24]);
+        bmp.extend([0, 0, 255, 0, 0, 255, 0, 0]);
+        bmp.extend([0, 255, 0, 9, 9, 9, 0, 0]);
+        let image = Image::from_bmp(bmp.as_slice()).unwrap();
+        assert_eq!(image.get(0, 0), [0, 255, 0]);
+        assert_eq!(image.get(1, 1), [255, 0, 0]);
+
+        let definitions = parse_definitions(b"province;red;green;blue;x;x\n1;255;0;0;Lorent;x\n2;0;255;0;Ionnidar;x\n");
+        let map = ProvinceMap::new(&image, &definitions);
+        assert_eq!(map.id_at(0, 0), 2);
+        assert_eq!(map.id_at(1, 0), 0);
+        assert_eq!(map.bounds.get(&1), Some(&(0, 1, 1, 1)));
+
+        assert_eq!(map.ids, vec![0, 2, 1]);
+
+        let rendered = map.render(|id| if id == 1 { Some([1, 2, 3]) } else { None });
+        // bottom row is province 1, the border is only drawn towards a different province
+        assert_eq!(rendered.get(0, 1), [1, 2, 3]);
+        assert_eq!(rendered.get(1, 1), [1, 2, 3]);
+
+        let locator = map.locator(1, |_| None, 0).unwrap();
+        assert_eq!((locator.width, locator.height), (2, 1));
+        assert_eq!(locator.get(0, 0), HIGHLIGHT);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use jomini::TextTape;
+
+use crate::countries::RACE_PERSONALITIES;
+use crate::cultures::CultureGroup;
+use crate::utils::gather;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RacialModifierKind {
+    Administration,
+    Military,
+    Tolerance,
+    Minority,
+}
+
+impl RacialModifierKind {
+    // The word in the modifier id that marks the kind, e.g. elven_administration
+    const ALL: [(&'static str, RacialModifierKind); 4] = [
+        ("administration", RacialModifierKind::Administration),
+        ("military", RacialModifierKind::Military),
+        ("tolerance", RacialModifierKind::Tolerance),
+        ("minority", RacialModifierKind::Minority),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RacialModifierKind::Administration => "Administration",
+            RacialModifierKind::Military => "Military",
+            RacialModifierKind::Tolerance => "Tolerance",
+            RacialModifierKind::Minority => "Minority",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RacialModifier {
+    pub id: String,
+    pub race: String,
+    pub kind: RacialModifierKind,
+    // whatever follows the kind, e.g. coexisting for elven_minority_coexisting
+    pub variant: String,
+    pub modifiers: BTreeMap<String, Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Race {
+    // as the modifier ids spell it, e.g. elven
+    pub id: String,
+    pub modifiers: Vec<RacialModifier>,
+    pub culture_groups: Vec<String>,
+}
+
+// Modifier ids use adjectives (elven, dwarven, gnomish) while the ruler personalities use
+// nouns (elf, dwarf, gnome), both reduce to the same stem
+pub fn race_stem(name: &str) -> String {
+    let mut stem = name.trim_end_matches("_personality").to_string();
+    for suffix in ["ish", "en", "ic", "e"] {
+        if stem.len() > suffix.len() + 2 && stem.ends_with(suffix) {
+            stem.truncate(stem.len() - suffix.len());
+            break;
+        }
+    }
+    if stem.ends_with('v') {
+        stem.pop();
+        stem.push('f');
+    }
+    stem
+}
+
+// <race>_<kind>_<variant>, where the race is one a ruler personality can have
+fn split_modifier_id(id: &str) -> Option<(String, RacialModifierKind, String)> {
+    let words = id.split('_').collect::<Vec<_>>();
+    for (i, word) in words.iter().enumerate().skip(1) {
+        if let Some((_, kind)) = RacialModifierKind::ALL.iter().find(|(w, _)| w == word) {
+            let race = words[..i].join("_");
+            if !RACE_PERSONALITIES.iter().any(|p| race_stem(p) == race_stem(&race)) {
+                return None;
+            }
+            return Some((race, *kind, words[i + 1..].join("_")));
+        }
+    }
+    None
+}
+
+pub fn parse_racial_modifier_file(data: &[u8]) -> Vec<RacialModifier> {
+    let mut modifiers = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let id = key.read_string();
+        let Some((race, kind, variant)) = split_modifier_id(&id) else { continue };
+        let Ok(fields) = value.read_object() else { continue };
+        let mut modifier = RacialModifier { id, race, kind, variant, modifiers: BTreeMap::new() };
+        for (key, _op, value) in fields.fields() {
+            if key.read_str() == "picture" {
+                continue;
+            }
+            if let Ok(scalar) = value.read_scalar() {
+                modifier.modifiers.insert(key.read_string(), scalar.as_bytes().to_vec());
+            }
+        }
+        modifiers.push(modifier);
+    }
+    modifiers
+}
+
+// The racial modifiers from every event modifier file, grouped by race with the culture groups
+// assign_races gave that race
+pub fn parse_races(culture_groups: &[CultureGroup]) -> Vec<Race> {
+    let mut files: Vec<PathBuf> = vec![];
+    gather("./anbennar/common/event_modifiers".to_string(), &mut files);
+    files.sort();
+
+    let mut races: BTreeMap<String, Race> = BTreeMap::new();
+    for file in files {
+        let data = fs::read(file.as_path()).expect("error reading file");
+        for modifier in parse_racial_modifier_file(data.as_slice()) {
+            races.entry(modifier.race.clone())
+                .or_insert_with(|| Race { id: modifier.race.clone(), ..Default::default() })
+                .modifiers
+                .push(modifier);
+        }
+    }
+    for race in races.values_mut() {
+        race.modifiers.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.id.cmp(&b.id)));
+        race.culture_groups = culture_groups.iter()
+            .filter(|g| g.race.as_ref().is_some_and(|r| race_stem(r) == race_stem(&race.id)))
+            .map(|g| g.id.clone())
+            .collect();
+    }
+    races.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_race_stem() {
+        assert_eq!(race_stem("elven"), race_stem("elf_personality"));
+        assert_eq!(race_stem("dwarven"), race_stem("dwarf"));
+        assert_eq!(race_stem("gnomish"), race_stem("gnome"));
+        assert_eq!(race_stem("half_elven"), race_stem("half_elf"));
+        assert_eq!(race_stem("human"), "human");
+    }
+
+    #[test]
+    fn test_parse_racial_modifier_file() {
+        let modifiers = parse_racial_modifier_file(b"elven_administration = { global_tax_modifier = 0.1 picture = x }
+            half_elven_minority_coexisting = { global_unrest = -1 }
+            racial_pop_flag = { }
+            improved_military_drills = { land_morale = 0.1 }");
+        assert_eq!(modifiers.len(), 2);
+        assert_eq!(modifiers[0].race, "elven");
+        assert_eq!(modifiers[0].kind, RacialModifierKind::Administration);
+        assert_eq!(modifiers[0].modifiers.len(), 1);
+        assert_eq!(modifiers[1].race, "half_elven");
+        assert_eq!(modifiers[1].kind, RacialModifierKind::Minority);
+        assert_eq!(modifiers[1].variant, "coexisting");
+    }
+}
@@ -5,7 +5,10 @@ use jomini::{TextTape, Windows1252Encoding};
 use jomini::text::ValueReader;
 
 use crate::localisation::parse_all_localisations;
+use crate::map::ProvinceState;
 use crate::modifiers::get_modifier;
+use crate::triggers::parse_condition;
+use crate::utils::{read_bool, read_modifiers, read_strings};
 
 #[derive(Debug, Default)]
 pub struct ReligiousGroup {
@@ -29,7 +32,19 @@ pub struct Religion {
     pub holy_sites: Vec<u64>, // Option<>
     pub blessings: Vec<String>, // Option<>
     pub orthodox_icons: BTreeMap<String, OrthodoxIcons>, // Option<>
-    pub papacy: Option<Papacy>
+    pub papacy: Option<Papacy>,
+    pub personal_deity: bool, // see parse_personal_deities
+    pub fetishist_cult: bool, // see parse_fetishist_cults
+    pub ancestors: bool,
+    pub uses_karma: bool,
+    pub uses_harmony: bool,
+    pub fervor: bool,
+    pub doom: bool,
+    pub gurus: Vec<String>,
+    pub religious_reforms: bool, // see parse_religious_reforms
+    pub celebrate: Option<String>,
+    pub allowed_conversion: Vec<String>,
+    pub heretic: Vec<String>
     // etc.
 }
 
@@ -60,6 +75,42 @@ pub struct Concession {
     pub concilatory: BTreeMap<String, Vec<u8>>
 }
 
+#[derive(Debug, Default)]
+pub struct ChurchAspect {
+    pub id: String,
+    pub cost: Option<u64>,
+    pub modifiers: BTreeMap<String, Vec<u8>>
+}
+
+// Personal deities and fetishist cults share a layout: a sprite, the modifiers and triggers
+#[derive(Debug, Default)]
+pub struct Deity {
+    pub id: String,
+    pub sprite: Option<u64>,
+    pub modifiers: BTreeMap<String, Vec<u8>>,
+    // every religion named in potential or allow, empty when open to all
+    pub religions: Vec<String>
+}
+
+#[derive(Debug, Default)]
+pub struct ReligiousReform {
+    pub id: String,
+    pub modifiers: BTreeMap<String, Vec<u8>>
+}
+
+#[derive(Debug, Default)]
+pub struct ReligiousReforms {
+    pub id: String,
+    pub religions: Vec<String>,
+    pub reforms: Vec<ReligiousReform>
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Adherents {
+    pub provinces: usize,
+    pub development: u64
+}
+
 #[derive(Debug, Default)]
 pub struct CuriaInteraction {
     pub id: String,
@@ -100,7 +151,9 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
                     "flags_with_emblem_percentage" => {},
                     "flag_emblem_index_range" => {},
                     "ai_will_propagate_through_trade" => {},
-                    "center_of_religion" => {},
+                    "center_of_religion" => {
+                        religious_group.center_of_religion = value.read_scalar().ok().and_then(|s| s.to_u64().ok()).unwrap_or(0);
+                    },
                     "religious_schools" => {
                         parse_religious_schools(&mut religious_group, &value);
                     }
@@ -137,7 +190,7 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
                                             }
                                         }
                                     },
-                                    "allowed_conversion" => {},
+                                    "allowed_conversion" => religion.allowed_conversion = read_strings(&value),
                                     "country" => {
                                         let value = value.read_object();
                                         if value.is_ok() {
@@ -177,24 +230,19 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
                                     "allow_female_defenders_of_the_faith" => {},
                                     "uses_church_power" => {},
                                     "uses_anglican_power" => {},
-                                    "religious_reforms" => {
-                                        // TODO
-                                    },
-                                    "personal_deity" => {
-                                        // TODO
-                                    },
+                                    "religious_reforms" => religion.religious_reforms = read_bool(&value).unwrap_or(false),
+                                    "personal_deity" => religion.personal_deity = read_bool(&value).unwrap_or(false),
                                     "hre_religion" => {},
                                     "on_convert" => {},
                                     "allowed_center_conversion" => {},
                                     "will_get_center" => {},
                                     "date" => {},
-                                    "fetishist_cult" => {
-                                        // TODO
-                                    },
+                                    "fetishist_cult" => religion.fetishist_cult = read_bool(&value).unwrap_or(false),
                                     "ancestors" => {
                                         // basegame totemism
+                                        religion.ancestors = read_bool(&value).unwrap_or(false)
                                     },
-                                    "heretic" => {},
+                                    "heretic" => religion.heretic = read_strings(&value),
                                     "uses_judaism_power" => {
                                         // TODO
                                     },
@@ -228,91 +276,19 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
                                         // TODO
                                     },
                                     "reform_tooltip" => {},
-                                    "celebrate" => {
-                                        // TODO?
-                                    },
+                                    "celebrate" => religion.celebrate = value.read_string().ok(),
                                     "declare_war_in_regency" => {},
-                                    "doom" => {
-                                        // TODO
-                                    },
+                                    "doom" => religion.doom = read_bool(&value).unwrap_or(false),
                                     "hre_heretic_religion" => {},
-                                    "gurus" => {
-                                        // TODO immediate
-                                    },
+                                    "gurus" => religion.gurus = read_strings(&value),
                                     "papacy" => {
-                                        if let Ok(value) = value.read_object() {
-                                            for (key, _op, value) in value.fields() {
-                                                let key = key.read_str();
-                                                let key = key.as_ref();
-                                                let mut papacy = Papacy::default();
-                                                match key {
-                                                    "papal_tag" => {}
-                                                    "election_cost" => {}
-                                                    "seat_of_papacy" => {}
-                                                    "harsh" => {}
-                                                    "neutral" => {}
-                                                    "concilatory" => {}
-                                                    "concessions" => {
-                                                        /*if let Ok(value) = value.read_object() {
-                                                            for (key, _op, value) in value.fields() {
-                                                                //let key = key.read_str();
-                                                                //let key = key.as_ref();
-                                                                match key.read_string().as_ref() {
-                                                                    "potential_invite_scholar" => {},
-                                                                    "can_invite_scholar" => {},
-                                                                    "on_invite_scholar" => {},
-                                                                    "invite_scholar_modifier_display" => {},
-                                                                    "picture" => {
-                                                                        let value = value.read_string();
-                                                                        if value.is_ok() {
-                                                                            papacy.picture = Some(value.unwrap());
-                                                                        }
-                                                                    }
-                                                                    _ => {
-                                                                        // debug_modifiers(&key, &value);
-                                                                        let modifier = get_modifier(&key.read_string());
-                                                                        if modifier.is_some() {
-                                                                            papacy.country_modifiers.insert(key.read_string(), value.read_scalar().unwrap().as_bytes().to_vec());
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }*/
-                                                    }
-                                                    _ => {
-                                                        if let Ok(value) = value.read_object() {
-                                                            for (key, _op, value) in value.fields() {
-                                                                let key = key.read_str();
-                                                                let key = key.as_ref();
-                                                                let mut curia_interaction = CuriaInteraction::default();
-                                                                curia_interaction.id = key.to_string();
-                                                                match key {
-                                                                    "cost" => {},
-                                                                    "potential" => {},
-                                                                    "allow" => {},
-                                                                    "effect" => {},
-                                                                    "ai_will_do" => {}
-                                                                    _ => {
-                                                                        todo!()
-                                                                    }
-                                                                }
-
-                                                                papacy.curia_interaction.insert(key.to_string(), curia_interaction);
-                                                            }
-                                                        }
-                                                    }
-                                                }
-
-                                                religion.papacy = Some(papacy);
-                                            }
-                                        }
+                                        religion.papacy = Some(parse_papacy(&value));
                                     },
                                     "uses_karma" => {
-                                        // TODO in 00_static_modifiers
-                                    },
-                                    "uses_harmony" => {
-                                        // TODO
+                                        // the karma modifiers themselves are in 00_static_modifiers
+                                        religion.uses_karma = read_bool(&value).unwrap_or(false)
                                     },
+                                    "uses_harmony" => religion.uses_harmony = read_bool(&value).unwrap_or(false),
                                     "has_patriarchs" => {
                                         // TODO
                                     },
@@ -345,9 +321,7 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
                                             }
                                         }
                                     },
-                                    "fervor" => {
-                                        // TODO
-                                    },
+                                    "fervor" => religion.fervor = read_bool(&value).unwrap_or(false),
                                     "uses_hussite_power" => {
                                         // TODO
                                     },
@@ -373,6 +347,193 @@ fn parse_religious_groups_file(data: &[u8], localisations: Option<&HashMap<Strin
     religious_groups
 }
 
+// Modifiers written straight into the block, as deities and reforms do, plus any modifier = { }
+fn inline_modifiers(value: &ValueReader<Windows1252Encoding>) -> BTreeMap<String, Vec<u8>> {
+    let mut modifiers = BTreeMap::new();
+    if let Ok(value) = value.read_object() {
+        for (key, _op, value) in value.fields() {
+            if key.read_str() == "modifier" {
+                modifiers.extend(read_modifiers(&value));
+            } else if get_modifier(&key.read_string()).is_some() {
+                if let Ok(scalar) = value.read_scalar() {
+                    modifiers.insert(key.read_string(), scalar.as_bytes().to_vec());
+                }
+            }
+        }
+    }
+    modifiers
+}
+
+// Every religion = x in a trigger, leaving out the ones under a NOT
+fn mentioned_religions(value: &ValueReader<Windows1252Encoding>, religions: &mut Vec<String>) {
+    for religion in parse_condition(value).values("religion") {
+        if !religions.contains(&religion) {
+            religions.push(religion);
+        }
+    }
+}
+
+pub fn parse_deity_file(data: &[u8]) -> Vec<Deity> {
+    let mut deities = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let Ok(fields) = value.read_object() else { continue };
+        let mut deity = Deity { id: key.read_string(), modifiers: inline_modifiers(&value), ..Default::default() };
+        for (key, _op, value) in fields.fields() {
+            match key.read_str().as_ref() {
+                "sprite" => deity.sprite = value.read_scalar().ok().and_then(|s| s.to_u64().ok()),
+                "potential" | "allow" => mentioned_religions(&value, &mut deity.religions),
+                _ => {}
+            }
+        }
+        deities.push(deity);
+    }
+    deities
+}
+
+fn parse_deity_dir(dir: &str) -> Vec<Deity> {
+    let mut deities = vec![];
+    let Ok(paths) = fs::read_dir(dir) else { return deities };
+    let mut paths = paths.flatten().map(|p| p.path()).collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        let data = fs::read(path).expect("error reading file");
+        deities.extend(parse_deity_file(data.as_slice()));
+    }
+    deities
+}
+
+pub fn parse_personal_deities() -> Vec<Deity> {
+    parse_deity_dir("./anbennar/common/personal_deities")
+}
+
+pub fn parse_fetishist_cults() -> Vec<Deity> {
+    parse_deity_dir("./anbennar/common/fetishist_cults")
+}
+
+pub fn parse_religious_reforms_file(data: &[u8]) -> Vec<ReligiousReforms> {
+    let mut reform_sets = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let Ok(fields) = value.read_object() else { continue };
+        let mut reform_set = ReligiousReforms { id: key.read_string(), ..Default::default() };
+        for (key, _op, value) in fields.fields() {
+            match key.read_str().as_ref() {
+                "religion" => {
+                    if let Ok(religion) = value.read_string() {
+                        reform_set.religions.push(religion);
+                    }
+                }
+                "trigger" | "potential" | "can_buy_idea" => mentioned_religions(&value, &mut reform_set.religions),
+                "ai_will_do" | "allow" | "effect" | "removed_effect" => {}
+                reform => {
+                    if value.read_object().is_ok() {
+                        reform_set.reforms.push(ReligiousReform { id: reform.to_string(), modifiers: inline_modifiers(&value) });
+                    }
+                }
+            }
+        }
+        reform_sets.push(reform_set);
+    }
+    reform_sets
+}
+
+pub fn parse_religious_reforms() -> Vec<ReligiousReforms> {
+    let mut reform_sets = vec![];
+    let Ok(paths) = fs::read_dir("./anbennar/common/religious_reforms") else { return reform_sets };
+    for path in paths.flatten() {
+        let data = fs::read(path.path()).expect("error reading file");
+        reform_sets.extend(parse_religious_reforms_file(data.as_slice()));
+    }
+    reform_sets
+}
+
+fn parse_papacy(value: &ValueReader<Windows1252Encoding>) -> Papacy {
+    let mut papacy = Papacy::default();
+    let Ok(value) = value.read_object() else { return papacy };
+    for (key, _op, value) in value.fields() {
+        match key.read_str().as_ref() {
+            "papal_tag" => papacy.papal_tag = value.read_string().ok(),
+            "seat_of_papacy" => papacy.seat_of_papacy = value.read_scalar().ok().and_then(|s| s.to_u64().ok()),
+            "election_cost" | "harsh" | "neutral" | "concilatory" => {}
+            "concessions" => {
+                if let Ok(concessions) = value.read_object() {
+                    for (key, _op, value) in concessions.fields() {
+                        let mut concession = Concession::default();
+                        if let Ok(fields) = value.read_object() {
+                            for (key, _op, value) in fields.fields() {
+                                match key.read_str().as_ref() {
+                                    "harsh" => concession.harsh = read_modifiers(&value),
+                                    "concilatory" => concession.concilatory = read_modifiers(&value),
+                                    _ => {}
+                                }
+                            }
+                        }
+                        papacy.concessions.insert(key.read_string(), concession);
+                    }
+                }
+            }
+            interaction => {
+                let Ok(fields) = value.read_object() else { continue };
+                let mut curia_interaction = CuriaInteraction { id: interaction.to_string(), ..Default::default() };
+                for (key, _op, value) in fields.fields() {
+                    if key.read_str() == "cost" {
+                        curia_interaction.cost = value.read_scalar().ok().and_then(|s| s.to_u64().ok());
+                    }
+                }
+                papacy.curia_interaction.insert(interaction.to_string(), curia_interaction);
+            }
+        }
+    }
+    papacy
+}
+
+// Aspects and blessings are only listed by name in the religion, their modifiers live in
+// common/church_aspects
+pub fn parse_church_aspects_file(data: &[u8]) -> BTreeMap<String, ChurchAspect> {
+    let mut aspects = BTreeMap::new();
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let mut aspect = ChurchAspect { id: key.read_string(), ..Default::default() };
+        let Ok(fields) = value.read_object() else { continue };
+        for (key, _op, value) in fields.fields() {
+            match key.read_str().as_ref() {
+                "cost" => aspect.cost = value.read_scalar().ok().and_then(|s| s.to_u64().ok()),
+                "modifier" => aspect.modifiers = read_modifiers(&value),
+                _ => {}
+            }
+        }
+        aspects.insert(aspect.id.clone(), aspect);
+    }
+    aspects
+}
+
+pub fn parse_church_aspects() -> BTreeMap<String, ChurchAspect> {
+    let mut aspects = BTreeMap::new();
+    let Ok(paths) = fs::read_dir("./anbennar/common/church_aspects") else { return aspects };
+    for path in paths.flatten() {
+        let data = fs::read(path.path()).expect("error reading file");
+        aspects.extend(parse_church_aspects_file(data.as_slice()));
+    }
+    aspects
+}
+
+// Religion -> how many of the given provinces follow it and their combined development
+pub fn count_adherents<'a>(states: impl Iterator<Item = &'a ProvinceState>) -> BTreeMap<String, Adherents> {
+    let mut adherents: BTreeMap<String, Adherents> = BTreeMap::new();
+    for state in states {
+        if let Some(religion) = &state.religion {
+            let entry = adherents.entry(religion.clone()).or_default();
+            entry.provinces += 1;
+            entry.development += state.development();
+        }
+    }
+    adherents
+}
+
 fn parse_religious_schools(religious_group: &mut ReligiousGroup, value: &ValueReader<Windows1252Encoding>) {
     let mut schools_map = BTreeMap::new();
 
@@ -432,4 +593,94 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_parse_papacy_and_aspects() {
+        let groups = parse_religious_groups_file(b"christian = {
+            catholic = {
+                country = { prestige = 1 }
+                aspects = { organised_through_bishops }
+                papacy = {
+                    papal_tag = PAP
+                    seat_of_papacy = 118
+                    concessions = { council_indulgences = { harsh = { global_tax_modifier = 0.1 } concilatory = { prestige = 1 } } }
+                    levy_church_tax = { cost = 50 }
+                }
+            }
+        }", None);
+        let catholic = groups[0].religions.get("catholic").unwrap();
+        let papacy = catholic.papacy.as_ref().unwrap();
+        assert_eq!(papacy.papal_tag.as_deref(), Some("PAP"));
+        assert_eq!(papacy.seat_of_papacy, Some(118));
+        let concession = papacy.concessions.get("council_indulgences").unwrap();
+        assert!(concession.harsh.contains_key("global_tax_modifier"));
+        assert!(concession.concilatory.contains_key("prestige"));
+        assert_eq!(papacy.curia_interaction.get("levy_church_tax").unwrap().cost, Some(50));
+
+        let aspects = parse_church_aspects_file(b"organised_through_bishops = { cost = 100 modifier = { global_tax_modifier = 0.05 } effect = { } }");
+        assert_eq!(aspects.get("organised_through_bishops").unwrap().modifiers.len(), 1);
+    }
+
+    #[test]
+    pub fn test_parse_religion_mechanics() {
+        let groups = parse_religious_groups_file(b"pagan = {
+            regent_court = {
+                personal_deity = yes
+                uses_karma = no
+                heretic = { Castanorian Damish }
+                allowed_conversion = { corinite }
+                celebrate = regent_court_festival
+                gurus = { guru_one guru_two }
+                religious_reforms = yes
+            }
+            old_sun_cult = {
+                religious_reforms = no
+            }
+        }", None);
+        let religion = groups[0].religions.get("regent_court").unwrap();
+        assert!(religion.personal_deity);
+        assert!(!religion.uses_karma);
+        assert!(religion.religious_reforms);
+        assert_eq!(religion.heretic, vec!["Castanorian", "Damish"]);
+        assert_eq!(religion.allowed_conversion, vec!["corinite"]);
+        assert_eq!(religion.celebrate.as_deref(), Some("regent_court_festival"));
+        assert_eq!(religion.gurus.len(), 2);
+        assert!(!groups[0].religions.get("old_sun_cult").unwrap().religious_reforms);
+
+        let deities = parse_deity_file(b"corin = {
+            sprite = 3
+            land_morale = 0.1
+            potential = { OR = { religion = regent_court religion = corinite } NOT = { religion = old_sun_cult } }
+            effect = { }
+        }");
+        assert_eq!(deities[0].sprite, Some(3));
+        assert_eq!(deities[0].modifiers.len(), 1);
+        assert_eq!(deities[0].religions, vec!["regent_court", "corinite"]);
+
+        let reforms = parse_religious_reforms_file(b"norse_reforms = {
+            trigger = { religion = norse_pagan_reformed }
+            can_buy_idea = { }
+            codify_the_faith = { icon = x global_tax_modifier = 0.1 }
+        }");
+        assert_eq!(reforms[0].religions, vec!["norse_pagan_reformed"]);
+        assert_eq!(reforms[0].reforms.len(), 1);
+        assert!(reforms[0].reforms[0].modifiers.contains_key("global_tax_modifier"));
+    }
+
+    #[test]
+    pub fn test_count_adherents() {
+        let states = [
+            ProvinceState { religion: Some("regent_court".to_string()), base_tax: Some(3), base_production: Some(3), base_manpower: Some(2), ..Default::default() },
+            ProvinceState { religion: Some("regent_court".to_string()), base_tax: Some(1), ..Default::default() },
+            ProvinceState { religion: Some("corinite".to_string()), base_manpower: Some(4), ..Default::default() },
+            ProvinceState::default(),
+        ];
+        let adherents = count_adherents(states.iter());
+        assert_eq!(adherents.len(), 2);
+        assert_eq!(adherents.get("regent_court"), Some(&Adherents { provinces: 2, development: 9 }));
+        assert_eq!(adherents.get("corinite"), Some(&Adherents { provinces: 1, development: 4 }));
+
+        let groups = parse_religious_groups_file(b"cannorian = { center_of_religion = 8 regent_court = { } }", None);
+        assert_eq!(groups[0].center_of_religion, 8);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+
+use jomini::common::PdsDate;
+use jomini::text::Operator;
+
+use crate::countries::{CountryHistory, CountryTimeline, parse_country_timelines};
+use crate::cultures::parse_culture_groups;
+use crate::map::{parse_province_histories, ProvinceHistory, ProvinceState};
+use crate::religions::parse_religious_groups;
+use crate::triggers::{compare, Condition};
+use crate::utils::start_date;
+
+// The game on the start date as far as the history files tell, enough to answer the triggers
+// that only look at tags, cultures, religions, governments and owned provinces
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub countries: HashMap<String, CountryHistory>,
+    pub provinces: BTreeMap<u64, ProvinceState>,
+    // culture -> culture group
+    pub culture_groups: BTreeMap<String, String>,
+    // religion -> religious group
+    pub religion_groups: HashMap<String, String>,
+    // tag -> ids of the provinces it owns
+    owned: HashMap<String, Vec<u64>>,
+    // stands in for tags without a history file
+    empty: CountryHistory,
+}
+
+impl Snapshot {
+    pub fn new(
+        countries: HashMap<String, CountryHistory>,
+        provinces: BTreeMap<u64, ProvinceState>,
+        culture_groups: BTreeMap<String, String>,
+        religion_groups: HashMap<String, String>,
+    ) -> Snapshot {
+        let mut owned: HashMap<String, Vec<u64>> = HashMap::new();
+        for (id, state) in provinces.iter() {
+            if let Some(owner) = &state.owner {
+                owned.entry(owner.clone()).or_default().push(*id);
+            }
+        }
+        Snapshot { countries, provinces, culture_groups, religion_groups, owned, empty: CountryHistory::default() }
+    }
+
+    pub fn from_histories(
+        timelines: &HashMap<String, CountryTimeline>,
+        province_histories: &BTreeMap<u64, ProvinceHistory>,
+        culture_groups: BTreeMap<String, String>,
+        religion_groups: HashMap<String, String>,
+    ) -> Snapshot {
+        Snapshot::new(
+            timelines.iter().map(|(tag, t)| (tag.clone(), t.state_at(start_date()))).collect(),
+            province_histories.iter().map(|(id, h)| (*id, h.state_at(start_date()))).collect(),
+            culture_groups,
+            religion_groups,
+        )
+    }
+
+    pub fn at_start() -> Snapshot {
+        let (province_histories, _errors) = parse_province_histories();
+        let religion_groups = parse_religious_groups(None)
+            .into_iter()
+            .flat_map(|group| {
+                let id = group.id;
+                group.religions.into_keys().map(move |religion| (religion, id.clone()))
+            })
+            .collect();
+        Snapshot::from_histories(&parse_country_timelines(), &province_histories, parse_culture_groups(), religion_groups)
+    }
+
+    pub fn country(&self, tag: &str) -> &CountryHistory {
+        self.countries.get(tag).unwrap_or(&self.empty)
+    }
+
+    pub fn owned(&self, tag: &str) -> &[u64] {
+        self.owned.get(tag).map(|p| p.as_slice()).unwrap_or(&[])
+    }
+
+    // Answers country scope trigger leaves for `tag`, None for anything that depends on more
+    // than the history files
+    pub fn leaf<'a>(&'a self, tag: &'a str) -> impl Fn(&str, Option<Operator>, &str) -> Option<bool> + 'a {
+        let history = self.country(tag);
+        let history_leaf = history.leaf(tag, self.culture_groups.get(&history.primary_culture));
+        move |key: &str, op: Option<Operator>, value: &str| -> Option<bool> {
+            match key {
+                // nobody has changed tag yet on the start date
+                "was_tag" => Some(value == tag),
+                "exists" => match value {
+                    "yes" | "no" => Some((value == "yes") == !self.owned(tag).is_empty()),
+                    other => Some(!self.owned(other).is_empty()),
+                },
+                "religion_group" => self.religion_groups.get(&history.religion).map(|group| value == group),
+                "owns" => {
+                    let id = value.parse::<u64>().ok()?;
+                    Some(self.provinces.get(&id).is_some_and(|p| p.owner.as_deref() == Some(tag)))
+                }
+                "capital" => Some(value.parse::<usize>().ok()? == history.capital),
+                "num_of_cities" => Some(compare(self.owned(tag).len(), op, value.parse().ok()?)),
+                "is_year" => Some(compare(start_date().year() as usize, op, value.parse().ok()?)),
+                _ => history_leaf(key, op, value),
+            }
+        }
+    }
+
+    pub fn evaluate(&self, tag: &str, condition: &Condition) -> Option<bool> {
+        condition.evaluate(&self.leaf(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jomini::TextTape;
+
+    use crate::triggers::parse_condition;
+
+    use super::*;
+
+    fn condition(data: &[u8]) -> Condition {
+        let tape = TextTape::from_slice(data).unwrap();
+        let reader = tape.windows1252_reader();
+        let (_key, _op, value) = reader.fields().next().unwrap();
+        parse_condition(&value)
+    }
+
+    #[test]
+    pub fn test_snapshot_evaluate() {
+        let lorent = CountryHistory {
+            primary_culture: "high_lorentish".to_string(),
+            religion: "regent_court".to_string(),
+            government: "monarchy".to_string(),
+            government_rank: 2,
+            capital: 67,
+            ..Default::default()
+        };
+        let snapshot = Snapshot::new(
+            HashMap::from([("A01".to_string(), lorent), ("A02".to_string(), CountryHistory::default())]),
+            BTreeMap::from([
+                (67, ProvinceState { owner: Some("A01".to_string()), ..Default::default() }),
+                (68, ProvinceState { owner: Some("A01".to_string()), ..Default::default() }),
+            ]),
+            BTreeMap::from([("high_lorentish".to_string(), "lencori".to_string())]),
+            HashMap::from([("regent_court".to_string(), "cannorian".to_string())]),
+        );
+
+        let potential = condition(b"potential = { culture_group = lencori religion_group = cannorian owns = 67 num_of_cities = 2 }");
+        assert_eq!(snapshot.evaluate("A01", &potential), Some(true));
+        assert_eq!(snapshot.evaluate("A02", &potential), Some(false));
+
+        // the old tag walkers counted A01 here
+        let negated = condition(b"potential = { NOT = { tag = A01 } exists = yes exists = A01 }");
+        assert_eq!(snapshot.evaluate("A01", &negated), Some(false));
+        assert_eq!(snapshot.evaluate("A02", &negated), Some(false));
+
+        let unknown = condition(b"potential = { OR = { tag = A02 has_country_flag = lorent_flag } }");
+        assert_eq!(snapshot.evaluate("A01", &unknown), None);
+        assert_eq!(snapshot.evaluate("A02", &unknown), Some(true));
+
+        let year = condition(b"potential = { is_year = 1500 }");
+        assert_eq!(snapshot.evaluate("A01", &year), Some(false));
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use jomini::TextTape;
+
+use crate::provincemap::{Colour, read_colour};
+use crate::utils::{gather, read_modifiers};
+
+#[derive(Debug, Default)]
+pub struct TradeNode {
+    pub id: String,
+    pub location: u64,
+    pub inland: bool,
+    // nodes without outgoing links, the trade has to be collected there
+    pub end: bool,
+    pub members: Vec<u64>,
+    // ids of the nodes trade flows to
+    pub outgoing: Vec<String>,
+    pub colour: Option<Colour>,
+}
+
+#[derive(Debug, Default)]
+pub struct TradeGood {
+    pub id: String,
+    pub base_price: Option<f64>,
+    pub colour: Option<Colour>,
+    // for the country producing the most
+    pub modifiers: BTreeMap<String, Vec<u8>>,
+    // for every province producing it
+    pub province_modifiers: BTreeMap<String, Vec<u8>>,
+}
+
+fn read_files(directory: &str) -> Vec<Vec<u8>> {
+    let mut files: Vec<PathBuf> = vec![];
+    gather(directory.to_string(), &mut files);
+    files.sort();
+    files.iter().map(|f| fs::read(f).expect("error reading file")).collect()
+}
+
+pub fn parse_trade_node_file(data: &[u8]) -> Vec<TradeNode> {
+    let mut nodes = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let mut node = TradeNode { id: key.read_string(), ..Default::default() };
+        if let Ok(value) = value.read_object() {
+            for (key, _op, value) in value.fields() {
+                match key.read_str().as_ref() {
+                    "location" => node.location = value.read_scalar().ok().and_then(|v| v.to_u64().ok()).unwrap_or(0),
+                    "inland" => node.inland = value.read_str().is_ok_and(|v| v == "yes"),
+                    "end" => node.end = value.read_str().is_ok_and(|v| v == "yes"),
+                    "color" => node.colour = read_colour(&value),
+                    "members" => {
+                        if let Ok(members) = value.read_array() {
+                            node.members.extend(members.values().filter_map(|v| v.read_scalar().ok()?.to_u64().ok()));
+                        }
+                    }
+                    "outgoing" => {
+                        if let Ok(outgoing) = value.read_object() {
+                            for (k, _op, v) in outgoing.fields() {
+                                if k.read_str() == "name" {
+                                    if let Ok(name) = v.read_string() {
+                                        node.outgoing.push(name);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        nodes.push(node);
+    }
+    nodes
+}
+
+pub fn parse_trade_nodes() -> Vec<TradeNode> {
+    read_files("./anbennar/common/tradenodes")
+        .iter()
+        .flat_map(|data| parse_trade_node_file(data.as_slice()))
+        .collect()
+}
+
+pub fn parse_trade_good_file(data: &[u8]) -> Vec<TradeGood> {
+    let mut goods = vec![];
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        let mut good = TradeGood { id: key.read_string(), ..Default::default() };
+        if let Ok(value) = value.read_object() {
+            for (key, _op, value) in value.fields() {
+                match key.read_str().as_ref() {
+                    "color" => good.colour = read_colour(&value),
+                    "modifier" => good.modifiers = read_modifiers(&value),
+                    "province" => good.province_modifiers = read_modifiers(&value),
+                    _ => {}
+                }
+            }
+        }
+        goods.push(good);
+    }
+    goods
+}
+
+// common/prices: good = { base_price = 2 }
+pub fn parse_prices(data: &[u8]) -> BTreeMap<String, f64> {
+    let mut prices = BTreeMap::new();
+    let tape = TextTape::from_slice(data).unwrap();
+    let reader = tape.windows1252_reader();
+    for (key, _op, value) in reader.fields() {
+        if let Ok(value) = value.read_object() {
+            for (k, _op, v) in value.fields() {
+                if k.read_str() == "base_price" {
+                    if let Some(price) = v.read_scalar().ok().and_then(|v| v.to_f64().ok()) {
+                        prices.insert(key.read_string(), price);
+                    }
+                }
+            }
+        }
+    }
+    prices
+}
+
+pub fn parse_trade_goods() -> Vec<TradeGood> {
+    let mut prices = BTreeMap::new();
+    for data in read_files("./anbennar/common/prices") {
+        prices.extend(parse_prices(data.as_slice()));
+    }
+    let mut goods = read_files("./anbennar/common/tradegoods")
+        .iter()
+        .flat_map(|data| parse_trade_good_file(data.as_slice()))
+        .collect::<Vec<_>>();
+    for good in goods.iter_mut() {
+        good.base_price = prices.get(&good.id).copied();
+    }
+    goods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trade_nodes() {
+        let nodes = parse_trade_node_file(b"lencenor = {
+            location = 60
+            inland = yes
+            outgoing = { name = \"west_dameshead\" path = { 60 61 } control = { 1.0 2.0 } }
+            outgoing = { name = \"the_pearlescent_sea\" path = { 60 62 } control = { 1.0 2.0 } }
+            members = { 60 61 62 }
+        }
+        the_pearlescent_sea = { location = 900 end = yes members = { 900 } }");
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].inland);
+        assert_eq!(nodes[0].outgoing, vec!["west_dameshead", "the_pearlescent_sea"]);
+        assert_eq!(nodes[0].members, vec![60, 61, 62]);
+        assert!(nodes[1].end);
+    }
+
+    #[test]
+    fn test_parse_trade_goods() {
+        let goods = parse_trade_good_file(b"grain = { color = { 0.96 0.93 0.58 } modifier = { land_forcelimit_modifier = 0.2 } province = { local_monthly_devastation = -0.1 } chance = { factor = 1 } }");
+        assert_eq!(goods[0].colour, Some([245, 237, 148]));
+        assert_eq!(goods[0].modifiers.get("land_forcelimit_modifier").unwrap(), b"0.2");
+        assert_eq!(goods[0].province_modifiers.len(), 1);
+        let prices = parse_prices(b"grain = { base_price = 2 }");
+        assert_eq!(prices.get("grain"), Some(&2.0));
+    }
+}
@@ -0,0 +1,238 @@
+use jomini::text::{Operator, ValueReader};
+use jomini::{TextToken, Windows1252Encoding};
+use serde_json::{Map, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    // NOT = { a b } is true when none of its children are
+    Not(Vec<Condition>),
+    Leaf {
+        key: String,
+        op: Option<Operator>,
+        value: String,
+    },
+    // Anything with a block that isn't a logical operator, e.g. capital_scope = { ... } or if = { ... }
+    Scope {
+        key: String,
+        conditions: Vec<Condition>,
+    },
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Condition::And(vec![])
+    }
+}
+
+impl Condition {
+    // Tags named anywhere in the tree outside of a NOT
+    pub fn tags(&self) -> Vec<String> {
+        self.values("tag")
+    }
+
+    // Every `key = value` in the tree outside of a NOT or a scope, in order and without repeats
+    pub fn values(&self, key: &str) -> Vec<String> {
+        let mut values = vec![];
+        self.collect_values(key, &mut values);
+        values
+    }
+
+    fn collect_values(&self, wanted: &str, values: &mut Vec<String>) {
+        match self {
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                for condition in conditions {
+                    condition.collect_values(wanted, values);
+                }
+            }
+            Condition::Leaf { key, value, .. } => {
+                if key == wanted && !values.contains(value) {
+                    values.push(value.to_string());
+                }
+            }
+            Condition::Not(_) | Condition::Scope { .. } => {}
+        }
+    }
+
+    // Same shape as jsonify() output so it can go through htmlify()
+    pub fn to_json(&self) -> Value {
+        match self {
+            Condition::And(conditions) | Condition::Or(conditions) | Condition::Not(conditions)
+            | Condition::Scope { conditions, .. } => {
+                let mut map = Map::new();
+                for condition in conditions {
+                    let key = match condition {
+                        Condition::And(_) => "AND".to_string(),
+                        Condition::Or(_) => "OR".to_string(),
+                        Condition::Not(_) => "NOT".to_string(),
+                        Condition::Leaf { key, .. } | Condition::Scope { key, .. } => key.to_string(),
+                    };
+                    let value = condition.to_json();
+                    match map.get_mut(&key) {
+                        Some(Value::Array(values)) => values.push(value),
+                        Some(existing) => {
+                            *existing = Value::Array(vec![existing.clone(), value]);
+                        }
+                        None => {
+                            map.insert(key, value);
+                        }
+                    }
+                }
+                Value::Object(map)
+            }
+            Condition::Leaf { op, value, .. } => match op {
+                Some(op) => Value::String(format!("{} {}", op, value)),
+                None => Value::String(value.to_string()),
+            },
+        }
+    }
+
+    // Three-valued evaluation: None when the outcome depends on something `leaf` can't answer
+    pub fn evaluate<F>(&self, leaf: &F) -> Option<bool>
+    where
+        F: Fn(&str, Option<Operator>, &str) -> Option<bool>,
+    {
+        match self {
+            Condition::And(conditions) => {
+                let mut result = Some(true);
+                for condition in conditions {
+                    match condition.evaluate(leaf) {
+                        Some(false) => return Some(false),
+                        None => result = None,
+                        Some(true) => {}
+                    }
+                }
+                result
+            }
+            Condition::Or(conditions) => {
+                let mut result = Some(false);
+                for condition in conditions {
+                    match condition.evaluate(leaf) {
+                        Some(true) => return Some(true),
+                        None => result = None,
+                        Some(false) => {}
+                    }
+                }
+                result
+            }
+            Condition::Not(conditions) => {
+                Condition::Or(conditions.clone()).evaluate(leaf).map(|v| !v)
+            }
+            Condition::Leaf { key, op, value } => leaf(key, *op, value),
+            Condition::Scope { .. } => None,
+        }
+    }
+}
+
+// Numeric leaves like government_rank = 2 mean "at least" unless another operator is given
+pub fn compare(actual: usize, op: Option<Operator>, expected: usize) -> bool {
+    match op {
+        Some(Operator::LessThan) => actual < expected,
+        Some(Operator::LessThanEqual) => actual <= expected,
+        Some(Operator::GreaterThan) => actual > expected,
+        Some(Operator::Exact) => actual == expected,
+        Some(Operator::NotEqual) => actual != expected,
+        _ => actual >= expected,
+    }
+}
+
+pub fn parse_condition(value: &ValueReader<Windows1252Encoding>) -> Condition {
+    Condition::And(parse_conditions(value))
+}
+
+fn parse_conditions(value: &ValueReader<Windows1252Encoding>) -> Vec<Condition> {
+    let mut conditions = vec![];
+    if let Ok(value) = value.read_object() {
+        for (key, op, value) in value.fields() {
+            let key = key.read_string();
+            match value.token() {
+                TextToken::Object { .. } => {
+                    let children = parse_conditions(&value);
+                    match key.as_str() {
+                        "AND" => conditions.push(Condition::And(children)),
+                        "OR" => conditions.push(Condition::Or(children)),
+                        "NOT" => conditions.push(Condition::Not(children)),
+                        "custom_trigger_tooltip" => {
+                            let children = children.into_iter()
+                                .filter(|c| !matches!(c, Condition::Leaf { key, .. } if key == "tooltip"))
+                                .collect();
+                            conditions.push(Condition::And(children));
+                        }
+                        _ => conditions.push(Condition::Scope { key, conditions: children }),
+                    }
+                }
+                TextToken::Array { .. } => {
+                    let mut values = vec![];
+                    if let Ok(array) = value.read_array() {
+                        for value in array.values() {
+                            if let Ok(value) = value.read_string() {
+                                values.push(value);
+                            }
+                        }
+                    }
+                    conditions.push(Condition::Leaf { key, op, value: values.join(" ") });
+                }
+                _ => {
+                    if let Ok(scalar) = value.read_string() {
+                        conditions.push(Condition::Leaf { key, op, value: scalar });
+                    }
+                }
+            }
+        }
+    }
+    conditions
+}
+
+#[cfg(test)]
+mod tests {
+    use jomini::TextTape;
+
+    use super::*;
+
+    fn parse(data: &[u8]) -> Condition {
+        let tape = TextTape::from_slice(data).unwrap();
+        let reader = tape.windows1252_reader();
+        let (_key, _op, value) = reader.fields().next().unwrap();
+        parse_condition(&value)
+    }
+
+    #[test]
+    pub fn test_parse_condition() {
+        let condition = parse(b"trigger = { OR = { tag = A01 tag = A02 } NOT = { tag = A03 } capital_scope = { region = lencenor_region } }");
+        assert_eq!(condition.tags(), vec!["A01", "A02"]);
+        match condition {
+            Condition::And(conditions) => {
+                assert_eq!(conditions.len(), 3);
+                assert!(matches!(conditions[2], Condition::Scope { .. }));
+            }
+            _ => panic!("expected AND"),
+        }
+    }
+
+    #[test]
+    pub fn test_condition_to_json() {
+        let condition = parse(b"trigger = { OR = { tag = A01 tag = A02 } num_of_cities > 5 }");
+        assert_eq!(condition.to_json().to_string(), r#"{"OR":{"tag":["A01","A02"]},"num_of_cities":"> 5"}"#);
+    }
+
+    #[test]
+    pub fn test_evaluate_condition() {
+        let condition = parse(b"trigger = { culture_group = lencori NOT = { tag = A03 } }");
+        let lorent = |key: &str, _op: Option<Operator>, value: &str| match key {
+            "tag" => Some(value == "A01"),
+            "culture_group" => Some(value == "lencori"),
+            _ => None,
+        };
+        assert_eq!(condition.evaluate(&lorent), Some(true));
+
+        let condition = parse(b"trigger = { OR = { has_country_flag = x tag = A01 } }");
+        assert_eq!(condition.evaluate(&lorent), Some(true));
+
+        let condition = parse(b"trigger = { has_country_flag = x tag = A01 }");
+        assert_eq!(condition.evaluate(&lorent), None);
+
+        let condition = parse(b"trigger = { has_country_flag = x tag = A02 }");
+        assert_eq!(condition.evaluate(&lorent), Some(false));
+    }
+}
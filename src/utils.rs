@@ -1,12 +1,51 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
+use jomini::common::Date;
 use jomini::json::{DuplicateKeyMode, JsonOptions};
 use jomini::text::ValueReader;
 use jomini::Windows1252Encoding;
 use serde_json::Value;
 
+use crate::modifiers::get_modifier;
+
+pub fn read_strings(value: &ValueReader<Windows1252Encoding>) -> Vec<String> {
+    match value.read_array() {
+        Ok(values) => values.values().filter_map(|v| v.read_string().ok()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+// yes/no, None for anything else
+pub fn read_bool(value: &ValueReader<Windows1252Encoding>) -> Option<bool> {
+    match value.read_str().ok()?.as_ref() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+// Keys that aren't in MODIFIERS are dropped, nothing could display them anyway
+pub fn read_modifiers(value: &ValueReader<Windows1252Encoding>) -> BTreeMap<String, Vec<u8>> {
+    let mut modifiers = BTreeMap::new();
+    if let Ok(value) = value.read_object() {
+        for (key, _op, value) in value.fields() {
+            if get_modifier(&key.read_string()).is_some() {
+                if let Ok(scalar) = value.read_scalar() {
+                    modifiers.insert(key.read_string(), scalar.as_bytes().to_vec());
+                }
+            }
+        }
+    }
+    modifiers
+}
+
+// 1444.11.11, the default start date for everything parsed from history
+pub fn start_date() -> Date {
+    Date::from_ymd(1444, 11, 11)
+}
+
 pub fn read_all_files_in_path(directory: String) -> Vec<PathBuf> {
     let mut results = Vec::new();
     let paths = fs::read_dir(directory).expect("Missing directory");
@@ -167,7 +206,7 @@ mod tests {
         for reform in reforms {
             if reform.potential.is_some() {
                 let potential = reform.potential.unwrap();
-                let json = serde_json::from_str::<Value>(potential.as_str()).unwrap();
+                let json = potential.to_json();
                 // println!("{}", reform.id);
                 // println!("{}", htmlify(&json));
             }